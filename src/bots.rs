@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::str::FromStr;
+use types::*;
+
+/// What to do with activity from accounts that look like bots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotHandling {
+    /// Don't classify anything, bots are counted like everyone else.
+    Keep,
+    /// Leave bot activity out of the results.
+    Drop,
+    /// Keep bot activity but mark rows with an is_bot column.
+    Flag,
+    /// Only keep bot activity, so it can be counted separately.
+    Only,
+}
+
+impl FromStr for BotHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BotHandling, String> {
+        match s {
+            "keep" => Ok(BotHandling::Keep),
+            "drop" => Ok(BotHandling::Drop),
+            "flag" => Ok(BotHandling::Flag),
+            "only" => Ok(BotHandling::Only),
+            _ => Err(format!(
+                "Unknown bot handling {}, should be keep, drop, flag or only",
+                s
+            )),
+        }
+    }
+}
+
+/// Decides if a GitHub account is a bot.
+///
+/// An account is a bot if it's on the deny list, has a bot style login such as `dependabot[bot]`
+/// or `renovate-bot`, or makes more than `max_events_per_hour` events in a single archive hour.
+/// Accounts on the allow list are never bots.
+#[derive(Debug, Clone)]
+pub struct BotClassifier {
+    pub allow: HashSet<String>,
+    pub deny: HashSet<String>,
+    pub max_events_per_hour: Option<usize>,
+}
+
+impl BotClassifier {
+    /// Build a classifier from BOT_ALLOWLIST and BOT_DENYLIST (comma separated logins) and
+    /// BOT_MAX_EVENTS_PER_HOUR.  The event rate check is off if BOT_MAX_EVENTS_PER_HOUR isn't set.
+    pub fn from_env() -> BotClassifier {
+        BotClassifier {
+            allow: logins_from_env("BOT_ALLOWLIST"),
+            deny: logins_from_env("BOT_DENYLIST"),
+            max_events_per_hour: match env::var("BOT_MAX_EVENTS_PER_HOUR") {
+                Ok(max) => Some(
+                    max.parse::<usize>()
+                        .expect("Please set BOT_MAX_EVENTS_PER_HOUR to an integer value"),
+                ),
+                Err(_) => None,
+            },
+        }
+    }
+
+    /// Is the login (or commit author email) a bot, going by the name alone.
+    pub fn is_bot_login(&self, login: &str) -> bool {
+        let login = login.to_lowercase();
+        if self.allow.contains(&login) {
+            return false;
+        }
+        if self.deny.contains(&login) {
+            return true;
+        }
+        // GitHub Apps are `name[bot]`, their commit emails are `123+name[bot]@users.noreply.github.com`
+        login.ends_with("[bot]") || login.contains("[bot]@") || login.ends_with("-bot")
+    }
+
+    /// Logins that made more events than allowed in these events, which should all be from the same hour.
+    pub fn high_rate_actors(&self, events: &[Event]) -> HashSet<String> {
+        let max_events = match self.max_events_per_hour {
+            Some(max) => max,
+            None => return HashSet::new(),
+        };
+        let mut event_counts: HashMap<&str, usize> = HashMap::new();
+        for event in events {
+            if let Some(ref login) = event.actor.login {
+                *event_counts.entry(login).or_insert(0) += 1;
+            }
+        }
+        event_counts
+            .into_iter()
+            .filter(|&(login, count)| count > max_events && !self.allow.contains(&login.to_lowercase()))
+            .map(|(login, _)| login.to_string())
+            .collect()
+    }
+}
+
+fn logins_from_env(name: &str) -> HashSet<String> {
+    match env::var(name) {
+        Ok(logins) => logins
+            .split(',')
+            .map(|login| login.trim().to_lowercase())
+            .filter(|login| login != "")
+            .collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bots::BotClassifier;
+
+    #[test]
+    fn bot_logins() {
+        let classifier = BotClassifier {
+            allow: vec!["friendly-bot".to_string()].into_iter().collect(),
+            deny: vec!["ci-runner".to_string()].into_iter().collect(),
+            max_events_per_hour: None,
+        };
+        assert!(classifier.is_bot_login("dependabot[bot]"));
+        assert!(classifier.is_bot_login("49699333+dependabot[bot]@users.noreply.github.com"));
+        assert!(classifier.is_bot_login("renovate-bot"));
+        assert!(classifier.is_bot_login("CI-Runner"));
+        assert!(!classifier.is_bot_login("friendly-bot"));
+        assert!(!classifier.is_bot_login("matthewkmayer"));
+    }
+
+    #[test]
+    fn busy_actors_are_bots() {
+        use types::Event;
+        let classifier = BotClassifier {
+            allow: vec!["friendly-bot".to_string()].into_iter().collect(),
+            deny: Default::default(),
+            max_events_per_hour: Some(2),
+        };
+        let mut events: Vec<Event> = Vec::new();
+        for login in &["busy", "busy", "busy", "friendly-bot", "friendly-bot", "friendly-bot", "quiet"] {
+            let mut event = Event::new();
+            event.actor.login = Some(login.to_string());
+            events.push(event);
+        }
+
+        let busy = classifier.high_rate_actors(&events);
+        assert_eq!(1, busy.len());
+        assert!(busy.contains("busy"));
+    }
+}
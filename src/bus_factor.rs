@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use types::*;
+use writers::TextRow;
+
+/// How concentrated a repo's commits are, over the whole input or for one period.
+/// The bus factor is the fewest authors that together made at least half of the commits.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BusFactor {
+    pub repo_id: i64,
+    pub period: Option<NaiveDate>,
+    pub committers: usize,
+    pub commits: usize,
+    pub top_n_share: f64,
+    pub bus_factor: usize,
+}
+
+/// Read commit counts back from rows of the commits mode's `repo_commit_counts` CSV or JSON lines output.
+pub fn commit_counts_from_rows(rows: &[TextRow]) -> Result<Vec<CommitCount>, String> {
+    rows.iter()
+        .map(|row| {
+            let repo_id = row.get("repo_id").and_then(|repo_id| repo_id.parse::<i64>().ok());
+            let period = row.get("period")
+                .and_then(|period| NaiveDate::parse_from_str(period, "%Y-%m-%d").ok());
+            let commits = row.get("commits").and_then(|commits| commits.parse::<i64>().ok());
+            match (repo_id, period, commits) {
+                (Some(repo_id), Some(period), Some(commits)) => Ok(CommitCount {
+                    repo_id: repo_id,
+                    author: row.get("author").cloned().unwrap_or_default(),
+                    period: period,
+                    commits: commits,
+                }),
+                _ => Err(format!("{:?} isn't a commit count", row)),
+            }
+        })
+        .collect()
+}
+
+/// Bus factor for each repo over all the counts, plus one for each period in them.
+/// `top_n` is how many of the biggest committers `top_n_share` is for.  Commits with an unknown (empty) author
+/// are left out, since they can't be credited to anyone.
+pub fn bus_factor_report(counts: &[CommitCount], top_n: usize) -> Vec<BusFactor> {
+    let mut by_repo: HashMap<(i64, Option<NaiveDate>), HashMap<&str, usize>> = HashMap::new();
+    for count in counts.iter().filter(|count| count.author != "") {
+        for period in vec![None, Some(count.period)] {
+            *by_repo
+                .entry((count.repo_id, period))
+                .or_insert_with(HashMap::new)
+                .entry(&count.author)
+                .or_insert(0) += count.commits as usize;
+        }
+    }
+
+    let mut report: Vec<BusFactor> = by_repo
+        .into_iter()
+        .map(|((repo_id, period), by_author)| {
+            let mut author_commits: Vec<usize> = by_author.values().cloned().collect();
+            author_commits.sort_by(|a, b| b.cmp(a));
+            let commits: usize = author_commits.iter().sum();
+            let top_n_commits: usize = author_commits.iter().take(top_n).sum();
+            let mut covered = 0;
+            let bus_factor = author_commits
+                .iter()
+                .take_while(|&&author_count| {
+                    let needed = covered * 2 < commits;
+                    covered += author_count;
+                    needed
+                })
+                .count();
+            BusFactor {
+                repo_id: repo_id,
+                period: period,
+                committers: author_commits.len(),
+                commits: commits,
+                top_n_share: top_n_commits as f64 / commits as f64,
+                bus_factor: bus_factor,
+            }
+        })
+        .collect();
+    report.sort_by(|a, b| (a.repo_id, a.period).cmp(&(b.repo_id, b.period)));
+    report
+}
+
+/// CSV with a header row.  The whole input period is an empty period column.
+pub fn bus_factor_csv(report: &[BusFactor]) -> String {
+    let mut csv = "repo_id,period,committers,commits,top_n_share,bus_factor\n".to_string();
+    for row in report {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.4},{}\n",
+            row.repo_id,
+            match row.period {
+                Some(period) => period.to_string(),
+                None => "".to_string(),
+            },
+            row.committers,
+            row.commits,
+            row.top_n_share,
+            row.bus_factor
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use bus_factor::{bus_factor_csv, bus_factor_report, commit_counts_from_rows};
+    use writers::read_csv;
+
+    #[test]
+    fn bus_factors() {
+        let rows = read_csv(
+            "repo_id,author,period,commits
+1,alice,2016-01-01,3
+1,bob,2016-02-01,2
+1,carol,2016-02-01,1
+1,,2016-02-01,40
+",
+        ).unwrap();
+        let counts = commit_counts_from_rows(&rows).unwrap();
+        assert_eq!(4, counts.len());
+        let report = bus_factor_report(&counts, 1);
+
+        assert_eq!(3, report.len());
+        assert_eq!(None, report[0].period);
+        assert_eq!(3, report[0].committers);
+        assert_eq!(6, report[0].commits);
+        assert_eq!(0.5, report[0].top_n_share);
+        assert_eq!(1, report[0].bus_factor);
+        assert_eq!(Some(NaiveDate::from_ymd(2016, 1, 1)), report[1].period);
+        assert_eq!(1, report[1].bus_factor);
+        assert_eq!(Some(NaiveDate::from_ymd(2016, 2, 1)), report[2].period);
+        assert_eq!(2, report[2].committers);
+        assert_eq!(1, report[2].bus_factor);
+
+        let csv = bus_factor_csv(&report[0..2]);
+        assert_eq!(
+            "repo_id,period,committers,commits,top_n_share,bus_factor\n1,,3,6,0.5000,1\n1,2016-01-01,1,3,1.0000,1\n",
+            csv
+        );
+
+        let rows = read_csv("repo_id,sha,author\n1,abc,alice\n").unwrap();
+        assert!(commit_counts_from_rows(&rows).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+extern crate base64;
+extern crate md5;
+extern crate sha2;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use self::sha2::{Digest, Sha256};
+
+/// Size and checksums of a file as it was written, in hex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checksums {
+    pub size: u64,
+    pub md5: String,
+    pub sha256: String,
+}
+
+impl Checksums {
+    pub fn of(bytes: &[u8]) -> Checksums {
+        let mut writer = ChecksumWriter::new(io::sink());
+        writer.write_all(bytes).expect("Couldn't checksum bytes");
+        writer.finish().1
+    }
+
+    /// Check a copy of the file has the same size and checksums.
+    pub fn verify(&self, bytes: &[u8]) -> Result<(), String> {
+        let found = Checksums::of(bytes);
+        if found.size != self.size {
+            return Err(format!("{} bytes, should be {}", found.size, self.size));
+        }
+        if found.md5 != self.md5 {
+            return Err(format!("MD5 is {}, should be {}", found.md5, self.md5));
+        }
+        if found.sha256 != self.sha256 {
+            return Err(format!("SHA-256 is {}, should be {}", found.sha256, self.sha256));
+        }
+        Ok(())
+    }
+}
+
+/// MD5 of the bytes in base64, for the Content-MD5 header S3 checks uploads against.
+pub fn content_md5(bytes: &[u8]) -> String {
+    base64::encode(&md5::compute(bytes).0)
+}
+
+/// Passes what's written on to another writer, keeping count of the bytes and checksums of them.
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    size: u64,
+    md5: md5::Context,
+    sha256: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> ChecksumWriter<W> {
+        ChecksumWriter {
+            inner: inner,
+            size: 0,
+            md5: md5::Context::new(),
+            sha256: Sha256::default(),
+        }
+    }
+
+    /// Hand back the writer and the checksums of everything written to it.
+    pub fn finish(self) -> (W, Checksums) {
+        let checksums = Checksums {
+            size: self.size,
+            md5: format!("{:x}", self.md5.compute()),
+            sha256: hex(&self.sha256.result()),
+        };
+        (self.inner, checksums)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.size += written as u64;
+        self.md5.consume(&buf[..written]);
+        self.sha256.input(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).expect("Couldn't write hex");
+    }
+    hex
+}
+
+/// Every file a run wrote and their checksums, by key.  It's written next to the run's output in `manifests/`
+/// so the files can be checked later.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub run: String,
+    pub files: BTreeMap<String, Checksums>,
+}
+
+impl Manifest {
+    pub fn new(run: &str) -> Manifest {
+        Manifest {
+            run: run.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: &str, checksums: Checksums) {
+        self.files.insert(key.to_string(), checksums);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use checksums::{content_md5, ChecksumWriter, Checksums};
+
+    #[test]
+    fn known_checksums() {
+        let empty = Checksums::of(b"");
+        assert_eq!(0, empty.size);
+        assert_eq!("d41d8cd98f00b204e9800998ecf8427e", empty.md5);
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            empty.sha256
+        );
+        assert_eq!("1B2M2Y8AsgTpgAmY7PhCfg==", content_md5(b""));
+
+        let mut writer = ChecksumWriter::new(Vec::new());
+        writer.write_all(b"INSERT INTO ").unwrap();
+        writer.write_all(b"repo_mapping;").unwrap();
+        let (bytes, checksums) = writer.finish();
+        assert_eq!(b"INSERT INTO repo_mapping;".to_vec(), bytes);
+        assert_eq!(Checksums::of(b"INSERT INTO repo_mapping;"), checksums);
+    }
+
+    #[test]
+    fn truncated_files_fail() {
+        let checksums = Checksums::of(b"INSERT INTO repo_mapping;");
+        assert_eq!(Ok(()), checksums.verify(b"INSERT INTO repo_mapping;"));
+        assert_eq!(
+            Err("12 bytes, should be 25".to_string()),
+            checksums.verify(b"INSERT INTO ")
+        );
+        assert!(checksums.verify(b"INSERT INTO repo_mappinG;").is_err());
+    }
+}
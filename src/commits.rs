@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use chrono::NaiveDate;
+use types::*;
+
+/// Counts commits over a whole run.  The same commit can be pushed many times (force pushes, pushing to another
+/// branch, etc.) so each repo and SHA is only counted the first time it's seen.  Those are kept as 64 bit hashes
+/// instead of the SHAs, which could skip a commit but is very unlikely to.
+#[derive(Debug, Clone, Default)]
+pub struct CommitCounter {
+    seen: HashSet<u64>,
+    counts: HashMap<(i64, String, NaiveDate), i64>,
+}
+
+impl CommitCounter {
+    pub fn new() -> CommitCounter {
+        CommitCounter {
+            seen: HashSet::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Count a commit in a period, unless it's already been counted.  Returns whether it was new.
+    pub fn add(&mut self, commit: &AuthoredCommit, period: NaiveDate) -> bool {
+        let mut hasher = DefaultHasher::new();
+        commit.repo_id.hash(&mut hasher);
+        commit.sha.hash(&mut hasher);
+        if !self.seen.insert(hasher.finish()) {
+            return false;
+        }
+        *self.counts
+            .entry((commit.repo_id, commit.author.clone(), period))
+            .or_insert(0) += 1;
+        true
+    }
+
+    /// Count commits that weren't listed so have no SHA or author.  They're counted with an empty author.
+    pub fn add_unlisted(&mut self, repo_id: i64, period: NaiveDate, commits: i64) {
+        *self.counts
+            .entry((repo_id, "".to_string(), period))
+            .or_insert(0) += commits;
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    pub fn commit_counts(&self) -> Vec<CommitCount> {
+        let mut counts: Vec<CommitCount> = self.counts
+            .iter()
+            .map(|(&(repo_id, ref author, period), &commits)| CommitCount {
+                repo_id: repo_id,
+                author: author.clone(),
+                period: period,
+                commits: commits,
+            })
+            .collect();
+        counts.sort();
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use commits::*;
+
+    #[test]
+    fn commits_counted_once_per_run() {
+        let mut counter = CommitCounter::new();
+        let first_batch = NaiveDate::from_ymd(2016, 1, 1);
+        let second_batch = NaiveDate::from_ymd(2017, 1, 1);
+        let commit = AuthoredCommit {
+            repo_id: 1,
+            sha: "abc".to_string(),
+            author: "alice@example.com".to_string(),
+        };
+
+        assert!(counter.add(&commit, first_batch));
+        // force pushed again in a later batch
+        assert!(!counter.add(&commit, second_batch));
+        // the same SHA in another repo is another commit
+        assert!(counter.add(&AuthoredCommit { repo_id: 2, ..commit.clone() }, second_batch));
+        counter.add_unlisted(1, second_batch, 3);
+
+        assert_eq!(2, counter.len());
+        assert_eq!(
+            vec![
+                CommitCount {
+                    repo_id: 1,
+                    author: "".to_string(),
+                    period: second_batch,
+                    commits: 3,
+                },
+                CommitCount {
+                    repo_id: 1,
+                    author: "alice@example.com".to_string(),
+                    period: first_batch,
+                    commits: 1,
+                },
+                CommitCount {
+                    repo_id: 2,
+                    author: "alice@example.com".to_string(),
+                    period: second_batch,
+                    commits: 1,
+                },
+            ],
+            counter.commit_counts()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+extern crate flate2;
+extern crate zstd;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use self::flate2::Compression;
+use self::flate2::read::GzDecoder;
+use self::flate2::write::GzEncoder;
+
+/// How output files are compressed: `none`, `gzip`, `gzip:<level>` with a level of 1 (fast), 6 (the default)
+/// or 9 (best), `zstd` or `zstd:<level>` with a level from 1 to 21.  Zstandard's default level is 3.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    None,
+    Gzip(Compression),
+    Zstd(i32),
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Codec, String> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let level = parts.next();
+        match (name, level) {
+            ("none", None) => Ok(Codec::None),
+            ("gzip", None) => Ok(Codec::Gzip(Compression::Default)),
+            ("gzip", Some("1")) => Ok(Codec::Gzip(Compression::Fast)),
+            ("gzip", Some("6")) => Ok(Codec::Gzip(Compression::Default)),
+            ("gzip", Some("9")) => Ok(Codec::Gzip(Compression::Best)),
+            ("gzip", Some(level)) => Err(format!("Unknown gzip level {}, should be 1, 6 or 9", level)),
+            ("zstd", None) => Ok(Codec::Zstd(3)),
+            ("zstd", Some(level)) => match level.parse::<i32>() {
+                Ok(level) if level >= 1 && level <= 21 => Ok(Codec::Zstd(level)),
+                _ => Err(format!("Unknown zstd level {}, should be 1 to 21", level)),
+            },
+            _ => Err(format!("Unknown compression {}, should be none, gzip, gzip:<level>, zstd or zstd:<level>", s)),
+        }
+    }
+}
+
+impl Codec {
+    /// What's added to the end of compressed files' names.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Codec::None => "",
+            Codec::Gzip(_) => ".gz",
+            Codec::Zstd(_) => ".zst",
+        }
+    }
+
+    /// Start compressing into `inner`.  Nothing is buffered beyond what the compressor needs.
+    pub fn encoder<W: Write>(&self, inner: W) -> io::Result<Encoder<W>> {
+        match *self {
+            Codec::None => Ok(Encoder::Plain(inner)),
+            Codec::Gzip(level) => Ok(Encoder::Gzip(GzEncoder::new(inner, level))),
+            Codec::Zstd(level) => zstd::Encoder::new(inner, level).map(Encoder::Zstd),
+        }
+    }
+}
+
+/// Compresses what's written to it with a `Codec`.  `finish` has to be called to write the end of the stream.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<W>),
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Encoder::Plain(ref mut inner) => inner.write(buf),
+            Encoder::Gzip(ref mut encoder) => encoder.write(buf),
+            Encoder::Zstd(ref mut encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Encoder::Plain(ref mut inner) => inner.flush(),
+            Encoder::Gzip(ref mut encoder) => encoder.flush(),
+            Encoder::Zstd(ref mut encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the end of the compressed stream and hand back what it was written to.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(inner) => Ok(inner),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Compress the bytes in memory.
+pub fn compress_bytes(codec: Codec, bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = codec.encoder(Vec::new())?;
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Read a file of our output as text, decompressing it if it ends in `.gz` or `.zst`.
+pub fn read_output_file(path: &Path) -> io::Result<String> {
+    let file = File::open(path)?;
+    let mut text = String::new();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => GzDecoder::new(file)?.read_to_string(&mut text)?,
+        Some("zst") => zstd::Decoder::new(file)?.read_to_string(&mut text)?,
+        _ => (&file).read_to_string(&mut text)?,
+    };
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use compression::flate2::read::GzDecoder;
+    use compression::zstd;
+    use compression::{compress_bytes, Codec};
+
+    #[test]
+    fn codecs() {
+        assert_eq!(Ok(Codec::Zstd(3)), "zstd".parse::<Codec>());
+        assert_eq!(Ok(Codec::Zstd(19)), "zstd:19".parse::<Codec>());
+        assert_eq!(Ok(Codec::None), "none".parse::<Codec>());
+        assert!("gzip:4".parse::<Codec>().is_err());
+        assert!("zstd:0".parse::<Codec>().is_err());
+        assert!("lz4".parse::<Codec>().is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = "INSERT INTO repo_mapping (repo_id, repo_name, event_timestamp) VALUES (1, 'foo/bar', '2017-01-01');\n"
+            .repeat(100);
+
+        let gzipped = compress_bytes("gzip:9".parse().unwrap(), text.as_bytes()).unwrap();
+        let mut gunzipped = String::new();
+        GzDecoder::new(&gzipped[..])
+            .unwrap()
+            .read_to_string(&mut gunzipped)
+            .unwrap();
+        assert_eq!(text, gunzipped);
+
+        let zstd_bytes = compress_bytes(Codec::Zstd(3), text.as_bytes()).unwrap();
+        assert!(zstd_bytes.len() < text.len());
+        assert_eq!(text.as_bytes(), &zstd::decode_all(&zstd_bytes[..]).unwrap()[..]);
+
+        assert_eq!(text.as_bytes(), &compress_bytes(Codec::None, text.as_bytes()).unwrap()[..]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use chrono::{DateTime, Utc};
+use serde_json;
+use types::*;
+
+/// A predicate over events, read from JSON.  Each field check is an object with one key:
+///
+/// ```json
+/// {"all": [
+///     {"event_type": "PullRequestEvent"},
+///     {"action": "closed"},
+///     {"repo": "rust-lang/*"},
+///     {"not": {"actor": "bors"}},
+///     {"after": "2016-06-01T00:00:00Z"}
+/// ]}
+/// ```
+///
+/// Repo, actor and org checks ignore case, like GitHub does.  Repo names can use `*` and `?` wildcards.
+/// `after` includes events at that time and `before` doesn't.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventFilter {
+    All(Vec<EventFilter>),
+    Any(Vec<EventFilter>),
+    Not(Box<EventFilter>),
+    EventType(String),
+    Repo(String),
+    Actor(String),
+    Org(String),
+    Action(String),
+    After(DateTime<Utc>),
+    Before(DateTime<Utc>),
+}
+
+impl EventFilter {
+    /// Read a filter from EVENT_FILTER, which is either the JSON itself or the path of a file with it in.
+    /// None if EVENT_FILTER isn't set.
+    pub fn from_env() -> Option<EventFilter> {
+        let filter = match env::var("EVENT_FILTER") {
+            Ok(filter) => filter,
+            Err(_) => return None,
+        };
+        let json = match filter.trim_start().starts_with('{') {
+            true => filter,
+            false => {
+                let mut json = String::new();
+                File::open(&filter)
+                    .and_then(|mut file| file.read_to_string(&mut json))
+                    .expect("Couldn't read the EVENT_FILTER file");
+                json
+            }
+        };
+        Some(serde_json::from_str(&json).expect("Please set EVENT_FILTER to a valid filter"))
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        match *self {
+            EventFilter::All(ref filters) => filters.iter().all(|filter| filter.matches(event)),
+            EventFilter::Any(ref filters) => filters.iter().any(|filter| filter.matches(event)),
+            EventFilter::Not(ref filter) => !filter.matches(event),
+            EventFilter::EventType(ref event_type) => event.event_type == *event_type,
+            EventFilter::Repo(ref pattern) => glob_matches(&pattern.to_lowercase(), &event.repo.name.to_lowercase()),
+            EventFilter::Actor(ref actor) => match event.actor.login {
+                Some(ref login) => login.to_lowercase() == actor.to_lowercase(),
+                None => false,
+            },
+            EventFilter::Org(ref org) => event.org_login().to_lowercase() == org.to_lowercase(),
+            EventFilter::Action(ref action) => match event.payload {
+                Some(Payload {
+                    action: Some(ref event_action),
+                    ..
+                }) => event_action == action,
+                _ => false,
+            },
+            EventFilter::After(time) => event.created_at >= time,
+            EventFilter::Before(time) => event.created_at < time,
+        }
+    }
+}
+
+/// Does the whole value match the pattern, where `*` is any run of characters and `?` is any one.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    // where the last star was and how much of the value it's taken up to
+    let mut star: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = star {
+            // let the star take one more character and try again
+            star = Some((star_p, star_v + 1));
+            p = star_p + 1;
+            v = star_v + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use filters::{glob_matches, EventFilter};
+    use types::{Event, Payload};
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("rust-lang/*", "rust-lang/rust"));
+        assert!(glob_matches("*/rust*", "rust-lang/rustup"));
+        assert!(glob_matches("foo/ba?", "foo/bar"));
+        assert!(!glob_matches("rust-lang/*", "rust-lang-nursery/rand"));
+        assert!(!glob_matches("foo/ba?", "foo/barn"));
+    }
+
+    #[test]
+    fn closed_prs_on_matching_repos() {
+        let filter: EventFilter = serde_json::from_str(
+            r#"{"all": [
+                {"event_type": "PullRequestEvent"},
+                {"action": "closed"},
+                {"repo": "rust-lang/*"},
+                {"not": {"actor": "bors"}},
+                {"after": "2017-01-01T00:00:00Z"}
+            ]}"#,
+        ).expect("filter should parse");
+
+        let mut event = Event::new();
+        event.event_type = "PullRequestEvent".to_string();
+        event.repo.name = "Rust-Lang/rust".to_string();
+        event.actor.login = Some("someone".to_string());
+        event.created_at = "2017-05-01T07:00:00Z".parse().unwrap();
+        // no payload, so no action:
+        assert!(!filter.matches(&event));
+
+        let payload_text = r#"{"action": "closed"}"#;
+        event.payload = Some(serde_json::from_str::<Payload>(payload_text).unwrap());
+        assert!(filter.matches(&event));
+
+        event.actor.login = Some("bors".to_string());
+        assert!(!filter.matches(&event));
+    }
+}
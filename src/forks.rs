@@ -0,0 +1,107 @@
+use std::collections::{HashMap, HashSet};
+use types::*;
+
+/// How many times a repo was forked and how deep it is in its fork network.
+/// A repo that isn't a fork (as far as we've seen) has a depth of 0, a fork of it has 1,
+/// a fork of that fork has 2 and so on.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct ForkStats {
+    pub repo_id: i64,
+    pub fork_count: usize,
+    pub depth: usize,
+}
+
+/// Work out fork counts and depths for every repo in the fork edges.
+/// Only the edges given are known, so a fork of a repo forked before the processed window looks
+/// like it's at depth 1.
+pub fn fork_stats(forks: &[ForkEvent]) -> Vec<ForkStats> {
+    let mut parents: HashMap<i64, i64> = HashMap::new();
+    let mut forks_of: HashMap<i64, HashSet<i64>> = HashMap::new();
+    for fork in forks {
+        // Keep the first parent seen if bad data says a repo was forked from two places.
+        parents.entry(fork.fork_repo_id).or_insert(fork.parent_repo_id);
+        forks_of
+            .entry(fork.parent_repo_id)
+            .or_insert_with(HashSet::new)
+            .insert(fork.fork_repo_id);
+    }
+
+    let mut repo_ids: Vec<i64> = parents.keys().chain(forks_of.keys()).cloned().collect();
+    repo_ids.sort();
+    repo_ids.dedup();
+
+    let mut depths: HashMap<i64, usize> = HashMap::new();
+    repo_ids
+        .iter()
+        .map(|repo_id| ForkStats {
+            repo_id: *repo_id,
+            fork_count: match forks_of.get(repo_id) {
+                Some(forks) => forks.len(),
+                None => 0,
+            },
+            depth: fork_depth(*repo_id, &parents, &mut depths),
+        })
+        .collect()
+}
+
+/// Walk up the parents until we hit a repo that isn't a fork or one we already know the depth of.
+fn fork_depth(repo_id: i64, parents: &HashMap<i64, i64>, depths: &mut HashMap<i64, usize>) -> usize {
+    let mut chain: Vec<i64> = Vec::new();
+    let mut current = repo_id;
+    let mut depth = loop {
+        if let Some(depth) = depths.get(&current) {
+            break *depth;
+        }
+        match parents.get(&current) {
+            // A loop in the edges is bad data: stop walking instead of going around forever.
+            Some(parent) if !chain.contains(parent) && *parent != repo_id => {
+                chain.push(current);
+                current = *parent;
+            }
+            _ => break 0,
+        }
+    };
+    depths.insert(current, depth);
+    while let Some(repo) = chain.pop() {
+        depth += 1;
+        depths.insert(repo, depth);
+    }
+    depths[&repo_id]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use forks::{fork_stats, ForkStats};
+    use types::ForkEvent;
+
+    #[test]
+    fn counts_and_depths() {
+        // 1 <- 2 <- 3, 1 <- 4, and a loop between 5 and 6 from bad data
+        let forks: Vec<ForkEvent> = [(1, 2), (2, 3), (1, 4), (1, 4), (5, 6), (6, 5)]
+            .iter()
+            .map(|&(parent_repo_id, fork_repo_id)| ForkEvent {
+                parent_repo_id: parent_repo_id,
+                fork_repo_id: fork_repo_id,
+                fork_owner: "someone".to_string(),
+                created_at: Utc.ymd(2016, 1, 1).and_hms(0, 0, 0),
+            })
+            .collect();
+        let stats = fork_stats(&forks);
+
+        assert_eq!(
+            ForkStats {
+                repo_id: 1,
+                fork_count: 2,
+                depth: 0,
+            },
+            stats[0]
+        );
+        assert_eq!(1, stats[1].depth);
+        assert_eq!(1, stats[1].fork_count);
+        assert_eq!(2, stats[2].depth);
+        assert_eq!(1, stats[3].depth);
+        assert_eq!(0, stats[3].fork_count);
+        assert_eq!(6, stats.len());
+    }
+}
@@ -10,14 +10,23 @@ use std::env;
 use std::{thread, time};
 use rusoto_core::{default_tls_client, DefaultCredentialsProviderSync, DispatchSignedRequest,
                   ProvideAwsCredentials, Region};
-use rusoto_s3::{GetObjectRequest, ListObjectsV2Request, S3, S3Client};
+use rusoto_s3::{GetObjectOutput, GetObjectRequest, ListObjectsV2Request, Object, S3, S3Client};
 use self::flate2::read::GzDecoder;
+use input_check::SourceObject;
 use types::*;
 
 const MAX_PAGE_SIZE: i64 = 500;
 
 /// Get list of files in the bucket, starting with the specified year and up to the number of hours specified.
 pub fn construct_list_of_ingest_files() -> Vec<String> {
+    list_ingest_objects()
+        .into_iter()
+        .map(|object| object.key)
+        .collect()
+}
+
+/// The files `construct_list_of_ingest_files` lists, with their sizes and ETags.
+pub fn list_ingest_objects() -> Vec<SourceObject> {
     // Get file list from S3:
     let bucket = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
     let year_to_process = env::var("GHAYEAR").expect("Need GHAYEAR set to year to process");
@@ -46,10 +55,10 @@ pub fn construct_list_of_ingest_files() -> Vec<String> {
     let result = client
         .list_objects_v2(&list_obj_req)
         .expect("Couldn't list items in bucket (v2)");
-    let mut files: Vec<String> = Vec::new();
+    let mut files: Vec<SourceObject> = Vec::new();
 
     for item in result.contents.expect("Should have list of items") {
-        files.push(item.key.expect("Key should exist for S3 item."));
+        files.push(source_object(item));
     }
 
     let mut more_to_go =
@@ -87,7 +96,7 @@ pub fn construct_list_of_ingest_files() -> Vec<String> {
             .expect("Couldn't list items in bucket (v2)");
 
         for item in inner_result.contents.expect("Should have list of items") {
-            files.push(item.key.expect("Key should exist for S3 item."));
+            files.push(source_object(item));
         }
         more_to_go = inner_result.next_continuation_token.is_some()
             && files.len() <= hours_to_process as usize;
@@ -100,6 +109,41 @@ pub fn construct_list_of_ingest_files() -> Vec<String> {
     files
 }
 
+fn source_object(item: Object) -> SourceObject {
+    SourceObject {
+        key: item.key.expect("Key should exist for S3 item."),
+        size: item.size,
+        e_tag: item.e_tag,
+    }
+}
+
+/// GHA files are named for the hour they cover, EG `2014-12-31-23.json.gz`.
+/// Files from before 2015 have a different layout.
+pub fn is_pre_2015_file(file_name: &str) -> bool {
+    match file_name.get(0..4).and_then(|year| year.parse::<i32>().ok()) {
+        Some(year) => year < 2015,
+        None => false,
+    }
+}
+
+/// Download the specified file and parse into events, converting pre-2015 events to the 2015 and later layout.
+pub fn download_and_parse_any_file<
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+>(
+    file_on_s3: &str,
+    client: &S3Client<P, D>,
+) -> Result<Vec<Event>, String> {
+    if !is_pre_2015_file(file_on_s3) {
+        return download_and_parse_file(file_on_s3, client);
+    }
+    let old_events = download_and_parse_old_file(file_on_s3, client)?;
+    Ok(old_events
+        .iter()
+        .filter_map(|old_event| old_event.as_event())
+        .collect())
+}
+
 /// Download the specified file and parse into pre-2015 events.
 pub fn download_and_parse_old_file<
     P: ProvideAwsCredentials + Sync + Send,
@@ -108,51 +152,7 @@ pub fn download_and_parse_old_file<
     file_on_s3: &str,
     client: &S3Client<P, D>,
 ) -> Result<Vec<Pre2015Event>, String> {
-    let bucket = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
-
-    let get_req = GetObjectRequest {
-        bucket: bucket.to_owned(),
-        key: file_on_s3.to_owned(),
-        ..Default::default()
-    };
-
-    let result = match client.get_object(&get_req) {
-        Ok(s3_result) => s3_result,
-        Err(_) => {
-            thread::sleep(time::Duration::from_millis(50));
-            match client.get_object(&get_req) {
-                Ok(s3_result) => s3_result,
-                Err(_) => {
-                    thread::sleep(time::Duration::from_millis(1000));
-                    match client.get_object(&get_req) {
-                        Ok(s3_result) => s3_result,
-                        Err(err) => {
-                            // if we get another error it's likely related to the connection pool
-                            // being in a weird state: make a new client which makes a new pool.
-                            println!(
-                                "Failed to get {:?} from S3, Third attempt: {:?}",
-                                file_on_s3, err
-                            );
-                            let client = S3Client::new(
-                                default_tls_client().expect("Couldn't make TLS client"),
-                                DefaultCredentialsProviderSync::new().expect(
-                                    "Couldn't get new copy of DefaultCredentialsProviderSync",
-                                ),
-                                Region::UsEast1,
-                            );
-                            match client.get_object(&get_req) {
-                                Ok(s3_result) => s3_result,
-                                Err(err) => {
-                                    return Err(format!("{:?}", err));
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    };
-
+    let result = get_gha_file(file_on_s3, client)?;
     let decoder = GzDecoder::new(result.body.expect("body should be preset"))
         .expect("Couldn't make a decoder");
     parse_ze_file_2014_older(BufReader::new(decoder))
@@ -166,25 +166,64 @@ pub fn download_and_parse_file<
     file_on_s3: &str,
     client: &S3Client<P, D>,
 ) -> Result<Vec<Event>, String> {
+    let result = get_gha_file(file_on_s3, client)?;
+    let decoder = GzDecoder::new(result.body.expect("body should be preset")).unwrap();
+    parse_ze_file_2015_newer(BufReader::new(decoder))
+}
+
+/// Download the specified file and parse into events, keeping the line of JSON each event came from.
+/// Pre-2015 events are converted like `download_and_parse_any_file` does but their lines are left as they were.
+pub fn download_and_parse_raw_file<
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+>(
+    file_on_s3: &str,
+    client: &S3Client<P, D>,
+) -> Result<Vec<RawEvent>, String> {
+    let result = get_gha_file(file_on_s3, client)?;
+    let decoder = GzDecoder::new(result.body.expect("body should be preset"))
+        .expect("Couldn't make a decoder");
+    parse_raw_lines(BufReader::new(decoder), is_pre_2015_file(file_on_s3))
+}
+
+/// Get a file from the GHA bucket, retrying if S3 kicks back errors.
+fn get_gha_file<
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+>(
+    file_on_s3: &str,
+    client: &S3Client<P, D>,
+) -> Result<GetObjectOutput, String> {
     let bucket = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
+    get_object_with_retries(&bucket, file_on_s3, client)
+}
 
+/// Get an object from S3, retrying if S3 kicks back errors.
+pub fn get_object_with_retries<
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+>(
+    bucket: &str,
+    file_on_s3: &str,
+    client: &S3Client<P, D>,
+) -> Result<GetObjectOutput, String> {
     let get_req = GetObjectRequest {
         bucket: bucket.to_owned(),
         key: file_on_s3.to_owned(),
         ..Default::default()
     };
 
-    let result = match client.get_object(&get_req) {
-        Ok(s3_result) => s3_result,
+    match client.get_object(&get_req) {
+        Ok(s3_result) => Ok(s3_result),
         Err(_) => {
             // Retry on error
             thread::sleep(time::Duration::from_millis(50));
             match client.get_object(&get_req) {
-                Ok(s3_result) => s3_result,
+                Ok(s3_result) => Ok(s3_result),
                 Err(_) => {
                     thread::sleep(time::Duration::from_millis(1000));
                     match client.get_object(&get_req) {
-                        Ok(s3_result) => s3_result,
+                        Ok(s3_result) => Ok(s3_result),
                         Err(err) => {
                             // if we get another error it's likely related to the connection pool
                             // being in a weird state: make a new client which makes a new pool.
@@ -199,20 +238,13 @@ pub fn download_and_parse_file<
                                 ),
                                 Region::UsEast1,
                             );
-                            match client.get_object(&get_req) {
-                                Ok(s3_result) => s3_result,
-                                Err(err) => {
-                                    return Err(format!("{:?}", err));
-                                }
-                            }
+                            client.get_object(&get_req).map_err(|err| format!("{:?}", err))
                         }
                     }
                 }
             }
         }
-    };
-    let decoder = GzDecoder::new(result.body.expect("body should be preset")).unwrap();
-    parse_ze_file_2015_newer(BufReader::new(decoder))
+    }
 }
 
 /// Deserialize pre-2015 events
@@ -244,3 +276,54 @@ fn parse_ze_file_2015_newer<R: BufRead>(mut contents: R) -> Result<Vec<Event>, S
 
     Ok(events)
 }
+
+/// Deserialize events of either layout, keeping their lines
+fn parse_raw_lines<R: BufRead>(mut contents: R, pre_2015: bool) -> Result<Vec<RawEvent>, String> {
+    let mut events: Vec<RawEvent> = Vec::new();
+    let mut line = String::new();
+    while contents.read_line(&mut line).unwrap() > 0 {
+        let event = match pre_2015 {
+            true => serde_json::from_str::<Pre2015Event>(&line).map(|old_event| old_event.as_event()),
+            false => serde_json::from_str::<Event>(&line).map(Some),
+        };
+        match event {
+            Ok(Some(event)) => events.push(RawEvent {
+                line: line.trim_end().to_string(),
+                event: event,
+            }),
+            Ok(None) => (),
+            Err(err) => println!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        line.clear();
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn pre_2015_files() {
+        use gha_sources::is_pre_2015_file;
+
+        assert!(is_pre_2015_file("2014-12-31-23.json.gz"));
+        assert!(is_pre_2015_file("2011-02-12-0.json.gz"));
+        assert!(!is_pre_2015_file("2015-01-01-0.json.gz"));
+        assert!(!is_pre_2015_file("x"));
+    }
+
+    #[test]
+    fn raw_lines_are_kept() {
+        use gha_sources::parse_raw_lines;
+
+        let lines = "{\"id\": \"1\", \"type\": \"WatchEvent\", \"actor\": {\"id\": 1, \"login\": \"a\"}, \"repo\": {\"id\": 5, \"name\": \"foo/bar\"}, \"created_at\": \"2017-05-01T07:00:00Z\"}\n\
+not json\n\
+{\"id\": \"2\", \"type\": \"WatchEvent\", \"actor\": {\"id\": 2, \"login\": \"b\"}, \"repo\": {\"id\": 6, \"name\": \"foo/baz\"}, \"created_at\": \"2017-05-01T07:00:01Z\"}\n";
+        let events = parse_raw_lines(lines.as_bytes(), false).unwrap();
+
+        assert_eq!(2, events.len());
+        assert_eq!(6, events[1].event.repo.id);
+        assert!(events[0].line.starts_with("{\"id\": \"1\""));
+        assert!(events[0].line.ends_with("}"));
+    }
+}
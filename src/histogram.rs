@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use chrono::{DateTime, Timelike, Utc};
+use types::*;
+
+/// Bits of the hash that pick a register, so 16,384 one byte registers and about 1% error.
+const SKETCH_PRECISION: u32 = 14;
+
+/// What the events in a window look like: how many of each type there were, each hour, and about how many
+/// different repos and actors made them.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HistogramSummary {
+    pub events: usize,
+    pub distinct_repos: usize,
+    pub distinct_actors: usize,
+    pub event_types: BTreeMap<String, usize>,
+    pub hours: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+/// Estimates how many distinct values it has seen in a fixed 16KB, however many there are (HyperLogLog).
+/// Close to exact for a few thousand values and within a couple of percent for millions.
+#[derive(Debug, Clone)]
+pub struct DistinctSketch {
+    registers: Vec<u8>,
+}
+
+impl Default for DistinctSketch {
+    fn default() -> DistinctSketch {
+        DistinctSketch::new()
+    }
+}
+
+impl DistinctSketch {
+    pub fn new() -> DistinctSketch {
+        DistinctSketch {
+            registers: vec![0; 1 << SKETCH_PRECISION],
+        }
+    }
+
+    pub fn add<T: Hash + ?Sized>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let register = (hash >> (64 - SKETCH_PRECISION)) as usize;
+        // position of the first one bit in the rest of the hash, with a stop bit so it's never past the end
+        let rank = ((hash << SKETCH_PRECISION) | (1 << (SKETCH_PRECISION - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+
+    pub fn estimate(&self) -> usize {
+        let registers = self.registers.len() as f64;
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        let sum: f64 = self.registers.iter().map(|&rank| 2f64.powi(-i32::from(rank))).sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / registers) * registers * registers / sum;
+        if estimate <= 2.5 * registers && empty > 0 {
+            // linear counting is better while there are empty registers
+            (registers * (registers / empty as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+/// Counts events as they go by, keeping only the counts and sketches of the repo IDs and actor logins seen.
+#[derive(Debug, Clone, Default)]
+pub struct EventHistogram {
+    counts: HashMap<(DateTime<Utc>, String), usize>,
+    repos: DistinctSketch,
+    actors: DistinctSketch,
+}
+
+impl EventHistogram {
+    pub fn new() -> EventHistogram {
+        EventHistogram {
+            counts: HashMap::new(),
+            repos: DistinctSketch::new(),
+            actors: DistinctSketch::new(),
+        }
+    }
+
+    pub fn add(&mut self, event: &Event) {
+        let hour = event
+            .created_at
+            .with_minute(0)
+            .and_then(|time| time.with_second(0))
+            .and_then(|time| time.with_nanosecond(0))
+            .unwrap_or(event.created_at);
+        *self.counts.entry((hour, event.event_type.clone())).or_insert(0) += 1;
+        self.repos.add(&event.repo.id);
+        if let Some(ref login) = event.actor.login {
+            self.actors.add(login.as_str());
+        }
+    }
+
+    pub fn summary(&self) -> HistogramSummary {
+        let mut summary = HistogramSummary {
+            events: 0,
+            distinct_repos: self.repos.estimate(),
+            distinct_actors: self.actors.estimate(),
+            event_types: BTreeMap::new(),
+            hours: BTreeMap::new(),
+        };
+        for (&(ref hour, ref event_type), &count) in &self.counts {
+            summary.events += count;
+            *summary.event_types.entry(event_type.clone()).or_insert(0) += count;
+            *summary
+                .hours
+                .entry(hour.format("%Y-%m-%dT%H:00:00Z").to_string())
+                .or_insert_with(BTreeMap::new)
+                .entry(event_type.clone())
+                .or_insert(0) += count;
+        }
+        summary
+    }
+}
+
+/// CSV of the hourly counts with a header row, one row per hour and event type.
+pub fn histogram_csv(summary: &HistogramSummary) -> String {
+    let mut csv = "hour,event_type,events\n".to_string();
+    for (hour, event_types) in &summary.hours {
+        for (event_type, count) in event_types {
+            csv.push_str(&format!("{},{},{}\n", hour, event_type, count));
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use histogram::{histogram_csv, DistinctSketch, EventHistogram};
+    use types::Event;
+
+    #[test]
+    fn counts_by_type_and_hour() {
+        let mut histogram = EventHistogram::new();
+        for &(event_type, repo_id, actor, hour, minute) in &[
+            ("PushEvent", 1, "alice", 0, 5),
+            ("PushEvent", 1, "alice", 0, 55),
+            ("WatchEvent", 2, "bob", 0, 10),
+            ("PushEvent", 3, "bob", 1, 0),
+        ] {
+            let mut event = Event::new();
+            event.event_type = event_type.to_string();
+            event.repo.id = repo_id;
+            event.actor.login = Some(actor.to_string());
+            event.created_at = Utc.ymd(2016, 1, 1).and_hms(hour, minute, 0);
+            histogram.add(&event);
+        }
+
+        let summary = histogram.summary();
+        assert_eq!(4, summary.events);
+        assert_eq!(3, summary.distinct_repos);
+        assert_eq!(2, summary.distinct_actors);
+        assert_eq!(Some(&3), summary.event_types.get("PushEvent"));
+        assert_eq!(
+            "hour,event_type,events\n2016-01-01T00:00:00Z,PushEvent,2\n2016-01-01T00:00:00Z,WatchEvent,1\n2016-01-01T01:00:00Z,PushEvent,1\n",
+            histogram_csv(&summary)
+        );
+    }
+
+    #[test]
+    fn distinct_estimates() {
+        let mut sketch = DistinctSketch::new();
+        for repo_id in 0..1000000i64 {
+            sketch.add(&repo_id);
+            sketch.add(&repo_id);
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!((estimate - 1000000.0).abs() < 20000.0, "estimated {}", estimate);
+        assert_eq!(0, DistinctSketch::new().estimate());
+    }
+}
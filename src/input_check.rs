@@ -0,0 +1,190 @@
+extern crate flate2;
+extern crate md5;
+
+use std::io::{BufRead, BufReader};
+use serde_json;
+use self::flate2::read::MultiGzDecoder;
+
+/// An hour file as the bucket listing has it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceObject {
+    pub key: String,
+    pub size: Option<i64>,
+    pub e_tag: Option<String>,
+}
+
+/// What checking an hour file found.  It's fine if there are no problems.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceCheck {
+    pub key: String,
+    pub size: usize,
+    pub lines: usize,
+    pub bad_lines: usize,
+    pub problems: Vec<String>,
+}
+
+/// Check a downloaded hour file against its listing and the download's ETag and length: the size, the ETag (the MD5
+/// of the file unless it was uploaded in parts), that it decompresses with the gzip checksums and lengths matching
+/// and that every line is a whole JSON object.
+pub fn check_source_object(
+    listed: &SourceObject,
+    e_tag: Option<&str>,
+    content_length: Option<i64>,
+    bytes: &[u8],
+) -> SourceCheck {
+    let mut check = SourceCheck {
+        key: listed.key.clone(),
+        size: bytes.len(),
+        lines: 0,
+        bad_lines: 0,
+        problems: Vec::new(),
+    };
+
+    if let Some(size) = listed.size {
+        if size != bytes.len() as i64 {
+            check
+                .problems
+                .push(format!("{} bytes, the listing says {}", bytes.len(), size));
+        }
+    }
+    if let Some(length) = content_length {
+        if length != bytes.len() as i64 {
+            check
+                .problems
+                .push(format!("download was {} bytes, S3 said {}", bytes.len(), length));
+        }
+    }
+    if let (Some(listed_tag), Some(tag)) = (listed.e_tag.as_ref(), e_tag) {
+        if listed_tag != tag {
+            check
+                .problems
+                .push(format!("ETag {} doesn't match the listing's {}", tag, listed_tag));
+        }
+    }
+    if let Some(tag) = e_tag.or(listed.e_tag.as_ref().map(|tag| tag.as_str())) {
+        let tag = tag.trim_matches('"');
+        let md5 = format!("{:x}", md5::compute(bytes));
+        // Multipart ETags are `<md5 of the parts' md5s>-<parts>` so there's nothing to compare.
+        if !tag.contains('-') && tag != md5 {
+            check.problems.push(format!("MD5 is {}, ETag is {}", md5, tag));
+        }
+    }
+
+    let decoder = match MultiGzDecoder::new(bytes) {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            check.problems.push(format!("not gzip: {}", e));
+            return check;
+        }
+    };
+    let mut reader = BufReader::new(decoder);
+    let mut line: Vec<u8> = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => {
+                check
+                    .problems
+                    .push(format!("gzip is broken after {} lines: {}", check.lines, e));
+                break;
+            }
+        }
+        if line.last() != Some(&b'\n') {
+            check.problems.push("last line has no line break".to_string());
+        }
+        if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+            continue;
+        }
+        check.lines += 1;
+        if serde_json::from_slice::<serde_json::Value>(&line).is_err() {
+            check.bad_lines += 1;
+        }
+    }
+    if check.bad_lines > 0 {
+        check
+            .problems
+            .push(format!("{} of {} lines aren't JSON", check.bad_lines, check.lines));
+    }
+    if check.lines == 0 && check.problems.is_empty() {
+        check.problems.push("no events".to_string());
+    }
+    check
+}
+
+/// The checks as CSV: key, bytes, lines, lines that aren't JSON and the problems found, separated by `; `.
+pub fn source_check_csv(checks: &[SourceCheck]) -> String {
+    let mut csv = "key,bytes,lines,bad_lines,problems\n".to_string();
+    for check in checks {
+        let problems = check.problems.join("; ").replace("\"", "\"\"");
+        csv.push_str(&format!(
+            "{},{},{},{},\"{}\"\n",
+            check.key, check.size, check.lines, check.bad_lines, problems
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use input_check::flate2::Compression;
+    use input_check::flate2::write::GzEncoder;
+    use input_check::md5;
+    use input_check::{check_source_object, SourceObject};
+
+    fn hour_file() -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::Default);
+        for id in 0..1000 {
+            writeln!(encoder, "{{\"id\":\"{}\",\"type\":\"PushEvent\"}}", id).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn listing(bytes: &[u8]) -> SourceObject {
+        SourceObject {
+            key: "2016-01-01-0.json.gz".to_string(),
+            size: Some(bytes.len() as i64),
+            e_tag: Some(format!("\"{:x}\"", md5::compute(bytes))),
+        }
+    }
+
+    #[test]
+    fn good_hour() {
+        let bytes = hour_file();
+        let listed = listing(&bytes);
+        let check = check_source_object(&listed, listed.e_tag.as_ref().map(|tag| tag.as_str()), None, &bytes);
+        assert_eq!(Vec::<String>::new(), check.problems);
+        assert_eq!(1000, check.lines);
+        assert_eq!(0, check.bad_lines);
+    }
+
+    #[test]
+    fn broken_hours() {
+        let bytes = hour_file();
+        let listed = listing(&bytes);
+
+        let truncated = &bytes[..bytes.len() - 4];
+        let check = check_source_object(&listed, None, Some(bytes.len() as i64), truncated);
+        assert_eq!(
+            vec![
+                format!("{} bytes, the listing says {}", truncated.len(), bytes.len()),
+                format!("download was {} bytes, S3 said {}", truncated.len(), bytes.len()),
+                format!("MD5 is {:x}, ETag is {:x}", md5::compute(truncated), md5::compute(&bytes)),
+                "gzip is broken after 1000 lines: corrupt gzip stream does not have a matching checksum".to_string(),
+            ],
+            check.problems
+        );
+
+        let mut corrupt = bytes.clone();
+        let middle = corrupt.len() / 2;
+        corrupt[middle] ^= 0xff;
+        let listed = SourceObject {
+            e_tag: Some("\"0123456789abcdef0123456789abcdef-2\"".to_string()),
+            ..listing(&corrupt)
+        };
+        let check = check_source_object(&listed, None, None, &corrupt);
+        assert!(check.problems.iter().any(|problem| problem.starts_with("gzip is broken")));
+    }
+}
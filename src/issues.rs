@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use types::*;
+
+/// How responsive a repo is on its issue tracker.  Pull requests aren't counted.
+/// First response is the first comment, label, close, etc by someone other than the issue's author.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct IssueStats {
+    pub repo_id: i64,
+    pub issues_opened: usize,
+    pub issues_closed: usize,
+    pub comments: usize,
+    pub responded_issues: usize,
+    pub median_first_response_seconds: Option<i64>,
+}
+
+/// What we know about one issue so far.
+#[derive(Debug, Clone)]
+struct IssueSummary {
+    opened: bool,
+    closed: bool,
+    comments: usize,
+    opened_at: Option<DateTime<Utc>>,
+    first_response_at: Option<DateTime<Utc>>,
+}
+
+/// Collects issue activity over a whole run, one small summary per issue.
+#[derive(Debug, Clone, Default)]
+pub struct IssueStatsCollector {
+    issues: HashMap<(i64, i64), IssueSummary>,
+}
+
+impl IssueStatsCollector {
+    pub fn new() -> IssueStatsCollector {
+        IssueStatsCollector {
+            issues: HashMap::new(),
+        }
+    }
+
+    /// Add an activity.  The same activity shouldn't be added twice or its comment is counted twice.
+    pub fn add(&mut self, activity: &IssueActivity) {
+        if activity.is_pull_request {
+            return;
+        }
+        let summary = self.issues
+            .entry((activity.repo_id, activity.number))
+            .or_insert(IssueSummary {
+                opened: false,
+                closed: false,
+                comments: 0,
+                opened_at: activity.opened_at,
+                first_response_at: None,
+            });
+        match activity.action.as_ref() {
+            "opened" => summary.opened = true,
+            "closed" => summary.closed = true,
+            "commented" => summary.comments += 1,
+            _ => (),
+        }
+        if activity.action != "opened" && activity.actor != activity.issue_author {
+            summary.first_response_at = match summary.first_response_at {
+                Some(first) if first <= activity.happened_at => Some(first),
+                _ => Some(activity.happened_at),
+            };
+        }
+    }
+
+    /// Stats for every repo we've seen issue activity for.
+    pub fn stats(&self) -> Vec<IssueStats> {
+        let mut by_repo: HashMap<i64, (IssueStats, Vec<i64>)> = HashMap::new();
+        for (&(repo_id, _), summary) in &self.issues {
+            let entry = by_repo.entry(repo_id).or_insert((
+                IssueStats {
+                    repo_id: repo_id,
+                    issues_opened: 0,
+                    issues_closed: 0,
+                    comments: 0,
+                    responded_issues: 0,
+                    median_first_response_seconds: None,
+                },
+                Vec::new(),
+            ));
+            if summary.opened {
+                entry.0.issues_opened += 1;
+            }
+            if summary.closed {
+                entry.0.issues_closed += 1;
+            }
+            entry.0.comments += summary.comments;
+            if let (Some(opened_at), Some(first_response_at)) = (summary.opened_at, summary.first_response_at) {
+                let seconds = first_response_at.signed_duration_since(opened_at).num_seconds();
+                // Responses from before the issue was opened are bad data.
+                if seconds >= 0 {
+                    entry.1.push(seconds);
+                }
+            }
+        }
+
+        let mut stats: Vec<IssueStats> = by_repo
+            .into_iter()
+            .map(|(_, (mut stats, mut response_times))| {
+                response_times.sort();
+                stats.responded_issues = response_times.len();
+                if !response_times.is_empty() {
+                    stats.median_first_response_seconds = Some(response_times[(response_times.len() - 1) / 2]);
+                }
+                stats
+            })
+            .collect();
+        stats.sort();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use issues::IssueStatsCollector;
+    use types::IssueActivity;
+
+    #[test]
+    fn first_response() {
+        let mut collector = IssueStatsCollector::new();
+        for &(number, action, actor, hour, is_pull_request) in &[
+            (1, "opened", "reporter", 0, false),
+            // the reporter commenting on their own issue isn't a response:
+            (1, "commented", "reporter", 1, false),
+            (1, "commented", "maintainer", 5, false),
+            (1, "labeled", "maintainer", 3, false),
+            (1, "closed", "maintainer", 6, false),
+            (2, "opened", "reporter", 0, false),
+            (3, "commented", "maintainer", 1, false),
+            (4, "commented", "maintainer", 1, true),
+        ] {
+            collector.add(&IssueActivity {
+                event_id: 1,
+                repo_id: 1,
+                number: number,
+                action: action.to_string(),
+                actor: actor.to_string(),
+                label: "".to_string(),
+                happened_at: Utc.ymd(2016, 1, 1).and_hms(hour, 0, 0),
+                issue_author: "reporter".to_string(),
+                opened_at: Some(Utc.ymd(2016, 1, 1).and_hms(0, 0, 0)),
+                is_pull_request: is_pull_request,
+            });
+        }
+
+        let stats = collector.stats();
+        assert_eq!(1, stats.len());
+        assert_eq!(2, stats[0].issues_opened);
+        assert_eq!(1, stats[0].issues_closed);
+        assert_eq!(3, stats[0].comments);
+        assert_eq!(2, stats[0].responded_issues);
+        assert_eq!(Some(3600), stats[0].median_first_response_seconds);
+    }
+}
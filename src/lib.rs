@@ -12,3 +12,63 @@ pub use types::*;
 
 pub mod gha_sources;
 pub use gha_sources::*;
+
+pub mod input_check;
+pub use input_check::*;
+
+pub mod bots;
+pub use bots::*;
+
+pub mod stars;
+pub use stars::*;
+
+pub mod commits;
+pub use commits::*;
+
+pub mod forks;
+pub use forks::*;
+
+pub mod pull_requests;
+pub use pull_requests::*;
+
+pub mod issues;
+pub use issues::*;
+
+pub mod releases;
+pub use releases::*;
+
+pub mod histogram;
+pub use histogram::*;
+
+pub mod filters;
+pub use filters::*;
+
+pub mod subset;
+pub use subset::*;
+
+pub mod repartition;
+pub use repartition::*;
+
+pub mod records;
+pub use records::*;
+
+pub mod parquet;
+pub use parquet::*;
+
+pub mod compression;
+pub use compression::*;
+
+pub mod checksums;
+pub use checksums::*;
+
+pub mod writers;
+pub use writers::*;
+
+pub mod sqlite;
+pub use sqlite::*;
+
+pub mod uploader;
+pub use uploader::*;
+
+pub mod bus_factor;
+pub use bus_factor::*;
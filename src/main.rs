@@ -13,65 +13,380 @@ extern crate serde_json;
 extern crate sha1;
 
 use std::io::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::sync::mpsc::sync_channel;
 use std::{thread, time};
 use std::str::FromStr;
 use rayon::prelude::*;
 use flate2::Compression;
-use flate2::write::GzEncoder;
 
 use rusty_von_humboldt::*;
+use chrono::{DateTime, NaiveDate, Utc};
 use rand::{thread_rng, Rng};
 use rusoto_core::{default_tls_client, DefaultCredentialsProviderSync, DispatchSignedRequest,
                   ProvideAwsCredentials, Region};
-use rusoto_s3::{PutObjectRequest, S3, S3Client};
+use rusoto_s3::{AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart,
+                CreateMultipartUploadRequest, PutObjectRequest, S3, S3Client, UploadPartRequest};
 
 const OBFUSCATE_COMMITTER_IDS: bool = true;
 
-/// MODE contains what mode to do: committer count or repo mappings as well as if it should
-/// upload results to s3 or not (dry run).
+/// Files bigger than this are uploaded in parts of this size.
+const UPLOAD_PART_SIZE: usize = 64 * 1024 * 1024;
+
+/// How many records' SQL is made at a time when writing SQL output.  A multiple of every statement's row count.
+const SQL_SLICE_RECORDS: usize = 100000;
+
+/// Modes that can be picked with the GHAMODE env var.
+const MODE_NAMES: [&str; 17] = [
+    "committers",
+    "repomapping",
+    "commits",
+    "stars",
+    "forks",
+    "pullrequests",
+    "issues",
+    "firstcontributions",
+    "orgs",
+    "releases",
+    "reviewers",
+    "histogram",
+    "subset",
+    "repartition",
+    "busfactor",
+    "verify",
+    "checkinput",
+];
+
+// MODE is what to do, from these env vars (the README has the details):
+// GHAMODE: one of MODE_NAMES, committers if it isn't set.
+// ATTRIBUTE_TO_AUTHORS: credit pushed commits to their authors instead of the pusher.
+// COMMITTER_BUCKET: count committers per day, week, month or year.
+// BOTS: keep, drop, flag or only committers that look like bots.
+// FORK_STATS, PR_STATS, ISSUE_STATS and RELEASE_STATS: add stats to the forks, pull requests, issues and releases modes.
+// ORG_FILTER: only process the events of a comma separated list of orgs.
+// OUTPUT_FORMAT: sql, parquet, csv, jsonl or sqlite.
+// OUTPUT_COMPRESSION: how SQL, CSV and JSON lines files are compressed.
+// DRYRUN: don't upload results to S3.
 lazy_static! {
+    static ref MODE_NAME: String = env::var("GHAMODE").unwrap_or("committers".to_string());
     static ref MODE: Mode = Mode {
-        committer_count: true,
-        repo_mapping: false,
-        dry_run: {
-            match env::var("DRYRUN"){
-                Ok(dryrun) => match bool::from_str(&dryrun) {
-                    Ok(should_dryrun) => should_dryrun,
-                    Err(_) => false,
-                },
-                Err(_) => false,
-            }
+        committer_count: *MODE_NAME == "committers",
+        repo_mapping: *MODE_NAME == "repomapping",
+        commit_count: *MODE_NAME == "commits",
+        stars: *MODE_NAME == "stars",
+        forks: *MODE_NAME == "forks",
+        fork_stats: bool_from_env("FORK_STATS"),
+        pull_requests: *MODE_NAME == "pullrequests",
+        pr_stats: bool_from_env("PR_STATS"),
+        issues: *MODE_NAME == "issues",
+        issue_stats: bool_from_env("ISSUE_STATS"),
+        first_contributions: *MODE_NAME == "firstcontributions",
+        orgs: *MODE_NAME == "orgs",
+        org_filter: match env::var("ORG_FILTER") {
+            Ok(orgs) => orgs
+                .split(',')
+                .map(|org| org.trim().to_lowercase())
+                .filter(|org| org != "")
+                .collect(),
+            Err(_) => HashSet::new(),
+        },
+        releases: *MODE_NAME == "releases",
+        release_stats: bool_from_env("RELEASE_STATS"),
+        reviewers: *MODE_NAME == "reviewers",
+        histogram: *MODE_NAME == "histogram",
+        subset: *MODE_NAME == "subset",
+        repartition: *MODE_NAME == "repartition",
+        bus_factor: *MODE_NAME == "busfactor",
+        verify: *MODE_NAME == "verify",
+        check_input: *MODE_NAME == "checkinput",
+        attribute_to_authors: bool_from_env("ATTRIBUTE_TO_AUTHORS"),
+        committer_bucket: match env::var("COMMITTER_BUCKET") {
+            Ok(bucket) => Some(bucket.parse::<TimeBucket>().expect("Please set COMMITTER_BUCKET to day, week, month or year")),
+            Err(_) => None,
+        },
+        bots: match env::var("BOTS") {
+            Ok(bots) => bots.parse::<BotHandling>().expect("Please set BOTS to keep, drop, flag or only"),
+            Err(_) => BotHandling::Keep,
         },
+        output_format: match env::var("OUTPUT_FORMAT") {
+            Ok(format) => format.parse::<OutputFormat>().expect("Please set OUTPUT_FORMAT to sql, parquet, csv, jsonl or sqlite"),
+            Err(_) => OutputFormat::Sql,
+        },
+        output_codec: match env::var("OUTPUT_COMPRESSION") {
+            Ok(codec) => codec.parse::<Codec>().expect(
+                "Please set OUTPUT_COMPRESSION to none, gzip, gzip:<level>, zstd or zstd:<level>",
+            ),
+            Err(_) => Codec::Gzip(Compression::Default),
+        },
+        dry_run: bool_from_env("DRYRUN"),
+    };
+    /// Rows in each Parquet file and in each row group in a file.
+    static ref PARQUET_FILE_ROWS: usize = usize_from_env("PARQUET_FILE_ROWS", 1000000);
+    static ref PARQUET_ROW_GROUP_SIZE: usize = usize_from_env("PARQUET_ROW_GROUP_SIZE", 100000);
+    /// The database SQLite output goes in, SQLITE_PATH or `rvh.sqlite` if that isn't set.
+    static ref SQLITE_SINK: Mutex<SqliteSink> = {
+        let path = env::var("SQLITE_PATH").unwrap_or("rvh.sqlite".to_string());
+        Mutex::new(SqliteSink::open(Path::new(&path)).expect("Couldn't open the SQLite database"))
     };
+    /// Results are written under OUTPUT_DIR, in the same layout as the destination bucket, if it's set.
+    static ref OUTPUT_DIR: Option<PathBuf> = env::var("OUTPUT_DIR").ok().map(PathBuf::from);
+    /// Uploads results in the background, UPLOAD_THREADS at a time with up to UPLOAD_QUEUE files waiting.
+    static ref UPLOADER: Uploader = Uploader::new(
+        usize_from_env("UPLOAD_THREADS", 4),
+        usize_from_env("UPLOAD_QUEUE", 8),
+        put_with_retries,
+    );
+    /// When this run started, which names its manifest.
+    static ref RUN_ID: String = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    /// Checksums of every file this run has written.
+    static ref MANIFEST: Mutex<Manifest> = Mutex::new(Manifest::new(&RUN_ID));
+    /// Table directories this run has written a JSON lines `schema.json` to.
+    static ref SCHEMAS_WRITTEN: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    static ref BOT_CLASSIFIER: BotClassifier = BotClassifier::from_env();
+    /// Only events matching EVENT_FILTER are processed, in every mode.
+    static ref EVENT_FILTER: Option<EventFilter> = EventFilter::from_env();
+    /// Repos the subset mode extracts, with renames from repo mapping output in SUBSET_REPO_MAPPING if it's set.
+    static ref REPO_SELECTOR: RepoSelector = {
+        let mut selector = RepoSelector::from_env();
+        if selector.is_empty() {
+            panic!("Please set SUBSET_ALLOW to the repos to extract, GHAMODE=repartition splits up the whole archive");
+        }
+        if let Ok(mapping_dir) = env::var("SUBSET_REPO_MAPPING") {
+            for file_name in table_files_in(Path::new(&mapping_dir)) {
+                let rows = read_table_file(&file_name).expect("Couldn't read repo mapping output");
+                for row in rows {
+                    match (row.get("repo_id").and_then(|id| id.parse::<i64>().ok()), row.get("repo_name")) {
+                        (Some(repo_id), Some(repo_name)) => selector.add_repo_mapping(repo_id, repo_name),
+                        _ => panic!("{} isn't repo mapping output", file_name.display()),
+                    }
+                }
+            }
+        }
+        selector
+    };
+    /// How the repartition mode splits up events, from REPARTITION_BY and REPARTITION_SHARDS.
+    static ref PARTITIONING: Partitioning = Partitioning::from_env();
+}
+
+/// Read a true/false env var, treating missing or unparseable values as false.
+fn bool_from_env(name: &str) -> bool {
+    match env::var(name) {
+        Ok(value) => match bool::from_str(&value) {
+            Ok(flag) => flag,
+            Err(_) => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Read a positive integer env var, using the default if it isn't set.
+fn usize_from_env(name: &str, default: usize) -> usize {
+    match env::var(name) {
+        Ok(value) => match value.parse::<usize>() {
+            Ok(number) if number > 0 => number,
+            _ => panic!("Please set {} to a positive integer value", name),
+        },
+        Err(_) => default,
+    }
 }
 
 fn main() {
     println!("Welcome to Rusty von Humboldt.");
-    environment_check();
-    sinker();
+    if MODE.bus_factor {
+        bus_factor_report_from_files();
+    } else if MODE.verify {
+        verify_output();
+    } else if MODE.check_input {
+        check_input_files();
+    } else {
+        environment_check();
+        sinker();
+    }
     println!("This is Rusty von Humboldt, heading home.");
 }
 
+/// Bus factor report over the commits mode's `repo_commit_counts` CSV or JSON lines output that's been downloaded to the
+/// REPORT_INPUT directory.  Written to REPORT_OUTPUT as CSV, or JSON if the name ends in `.json`.
+fn bus_factor_report_from_files() {
+    let input_dir = env::var("REPORT_INPUT").expect("Need REPORT_INPUT set to a directory of commit count output");
+    let output = env::var("REPORT_OUTPUT").unwrap_or("bus_factor.csv".to_string());
+    let top_n = match env::var("REPORT_TOP_N") {
+        Ok(top_n) => top_n
+            .parse::<usize>()
+            .expect("Please set REPORT_TOP_N to an integer value"),
+        Err(_) => 3,
+    };
+
+    let mut counts: Vec<CommitCount> = Vec::new();
+    for file_name in table_files_in(Path::new(&input_dir)) {
+        println!("Reading {}", file_name.display());
+        let rows = read_table_file(&file_name).expect("Couldn't read commit count output");
+        match commit_counts_from_rows(&rows) {
+            Ok(mut file_counts) => counts.append(&mut file_counts),
+            Err(e) => panic!("{} isn't repo_commit_counts output: {}", file_name.display(), e),
+        }
+    }
+
+    println!("Working out bus factors for {} commit counts", counts.len());
+    let report = bus_factor_report(&counts, top_n);
+    let contents = match output.ends_with(".json") {
+        true => serde_json::to_string_pretty(&report).expect("Couldn't make JSON of the report"),
+        false => bus_factor_csv(&report),
+    };
+    File::create(&output)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .expect("Couldn't write the report");
+    println!("Wrote {} rows to {}", report.len(), output);
+}
+
+/// Download every hour file a run would process and check it isn't broken, writing what was found for each file to
+/// INPUT_REPORT as CSV.  Broken files are reported rather than stopping the check, so every bad hour turns up in one pass.
+fn check_input_files() {
+    let bucket = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
+    let report = env::var("INPUT_REPORT").unwrap_or("input_check.csv".to_string());
+    let objects = list_ingest_objects();
+    println!("Checking {} files", objects.len());
+
+    let mut checks: Vec<SourceCheck> = objects
+        .par_chunks(10)
+        .flat_map(|chunk| {
+            let client = S3Client::new(
+                default_tls_client().expect("Couldn't make TLS client"),
+                DefaultCredentialsProviderSync::new()
+                    .expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
+                Region::UsEast1,
+            );
+            chunk
+                .iter()
+                .map(|listed| check_input_file(&bucket, listed, &client))
+                .collect::<Vec<SourceCheck>>()
+        })
+        .collect();
+    checks.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let broken: Vec<&SourceCheck> = checks.iter().filter(|check| !check.problems.is_empty()).collect();
+    for check in &broken {
+        println!("Bad file {}: {}", check.key, check.problems.join("; "));
+    }
+    File::create(&report)
+        .and_then(|mut file| file.write_all(source_check_csv(&checks).as_bytes()))
+        .expect("Couldn't write the input report");
+    println!(
+        "{} of {} files are broken and there are {} events in all.  Wrote the report to {}",
+        broken.len(),
+        checks.len(),
+        checks.iter().map(|check| check.lines).sum::<usize>(),
+        report
+    );
+}
+
+fn check_input_file<P, D>(bucket: &str, listed: &SourceObject, client: &S3Client<P, D>) -> SourceCheck
+where
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+{
+    let failed = |problem: String| SourceCheck {
+        key: listed.key.clone(),
+        size: 0,
+        lines: 0,
+        bad_lines: 0,
+        problems: vec![problem],
+    };
+    let object = match get_object_with_retries(bucket, &listed.key, client) {
+        Ok(object) => object,
+        Err(e) => return failed(format!("couldn't download: {}", e)),
+    };
+    let mut bytes = Vec::new();
+    if let Some(mut body) = object.body {
+        if let Err(e) = body.read_to_end(&mut bytes) {
+            return failed(format!("download broke off: {}", e));
+        }
+    }
+    check_source_object(
+        listed,
+        object.e_tag.as_ref().map(|tag| tag.as_str()),
+        object.content_length,
+        &bytes,
+    )
+}
+
+/// Every CSV and JSON lines file, compressed or not, in the directory and the directories in it.  Finding none is
+/// almost certainly the wrong directory or output format, so that stops the run instead of reading nothing.
+fn table_files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = find_table_files(dir);
+    if files.is_empty() {
+        panic!("No CSV or JSON lines output (.csv or .jsonl, compressed or not) in {}", dir.display());
+    }
+    files.sort();
+    files
+}
+
+fn find_table_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir).expect("Couldn't read directory") {
+        let path = entry.expect("Couldn't read directory entry").path();
+        if path.is_dir() {
+            files.append(&mut find_table_files(&path));
+        } else if table_file_format(&path).is_some() {
+            files.push(path);
+        }
+    }
+    files
+}
+
 /// Using channels to synchronize between sending threads and receiving thread.
 ///
 /// Spin up a receiving thread that takes Events from the channel. It consolidates/dedupes them, converts
-/// them to SQL then uploads to S3 when it has enough items collected. Behavior of committer count or
-/// repository ID mapping is controlled by the MODE lazy static.
+/// them to SQL then uploads to S3 when it has enough items collected. Behavior of committer count,
+/// commit count, stars, forks, pull requests, issues, first contributions, org rollups or repository ID mapping is
+/// controlled by the MODE lazy static.
 ///
 /// Sending threads (two threads) take the to-process file list and downloads, deserializes and sends
 /// to the channel.
 fn sinker() {
-    let dest_bucket = env::var("DESTBUCKET").expect("Need DESTBUCKET set to bucket name");
+    // Checked by environment_check, not needed when writing to OUTPUT_DIR.
+    let dest_bucket = env::var("DESTBUCKET").unwrap_or(String::new());
+    let manifest_bucket = dest_bucket.clone();
     // take the receive channel for file locations
     let mut file_list = make_list();
     let (send, recv) = sync_channel(1000000);
 
     // The receiving thread that accepts Events and converts them to the type needed.
-    let thread = thread::spawn(move || match MODE.committer_count {
-        true => do_work_son(recv, dest_bucket),
-        false => do_repo_work_son(recv, dest_bucket),
+    let thread = thread::spawn(move || {
+        if MODE.committer_count {
+            do_work_son(recv, dest_bucket)
+        } else if MODE.commit_count {
+            do_commit_work_son(recv, dest_bucket)
+        } else if MODE.stars {
+            do_stars_work_son(recv, dest_bucket)
+        } else if MODE.forks {
+            do_forks_work_son(recv, dest_bucket)
+        } else if MODE.pull_requests {
+            do_pr_work_son(recv, dest_bucket)
+        } else if MODE.issues {
+            do_issues_work_son(recv, dest_bucket)
+        } else if MODE.first_contributions {
+            do_first_contribution_work_son(recv, dest_bucket)
+        } else if MODE.orgs {
+            do_org_work_son(recv, dest_bucket)
+        } else if MODE.releases {
+            do_releases_work_son(recv, dest_bucket)
+        } else if MODE.reviewers {
+            do_reviewers_work_son(recv, dest_bucket)
+        } else if MODE.histogram {
+            do_histogram_work_son(recv, dest_bucket)
+        } else if MODE.subset {
+            do_subset_work_son(recv, dest_bucket)
+        } else if MODE.repartition {
+            do_repartition_work_son(recv, dest_bucket)
+        } else {
+            do_repo_work_son(recv, dest_bucket)
+        }
     });
 
     // send things all threaded like
@@ -88,15 +403,7 @@ fn sinker() {
             Region::UsEast1,
         );
         for file in file_list.chunks(10) {
-            let event_subset = match MODE.committer_count {
-                true => get_event_subset_committers(&file, &client),
-                false => get_event_subset(&file, &client),
-            };
-            for event in event_subset {
-                let event_item = EventWorkItem {
-                    event: event,
-                    no_more_work: false,
-                };
+            for event_item in get_event_subset_for_mode(&file, &client) {
                 send_a.send(event_item).expect("Should have sent event.");
             }
         }
@@ -110,15 +417,7 @@ fn sinker() {
             Region::UsEast1,
         );
         for file in second_file_list.chunks(10) {
-            let event_subset = match MODE.committer_count {
-                true => get_event_subset_committers(&file, &client),
-                false => get_event_subset(&file, &client),
-            };
-            for event in event_subset {
-                let event_item = EventWorkItem {
-                    event: event,
-                    no_more_work: false,
-                };
+            for event_item in get_event_subset_for_mode(&file, &client) {
                 send_b
                     .send(event_item)
                     .expect("Couldn't send event to channel b");
@@ -139,16 +438,23 @@ fn sinker() {
     println!("We're done sending items.");
     let event_item = EventWorkItem {
         event: Event::new(),
+        raw_line: None,
+        high_rate_actor: false,
         no_more_work: true,
     };
     send.send(event_item)
         .expect("Couldn't send stop work item.");
 
-    // Wait for the worker thread to wrap up.
+    // Wait for the worker thread to wrap up.  If it died its results are incomplete, so the run has failed.
     match thread.join() {
         Ok(_) => println!("Thread all wrapped up."),
-        Err(e) => println!("Thread didn't want to quit: {:?}", e),
+        Err(e) => panic!("Worker thread died: {:?}", e),
     }
+    // Then for its uploads to finish.
+    if let Err(e) = UPLOADER.finish() {
+        panic!("Uploading results failed: {}", e);
+    }
+    write_manifest(&manifest_bucket);
     println!("all wrapped up.");
 }
 
@@ -158,14 +464,11 @@ fn do_repo_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket:
     let mut wrap_things_up = false;
     let mut repo_mappings: Vec<RepoIdToName> = Vec::with_capacity(events_to_hold);
     let mut sql_collector: Vec<String> = Vec::new();
-    let mut sql_bytes: Vec<u8> = Vec::new();
     let mut index = 0;
-    // TODO: handle pre-2015 events
     loop {
         index += 1;
         repo_mappings.clear();
         sql_collector.clear();
-        sql_bytes.clear();
         if wrap_things_up {
             println!("wrapping thread up.");
             break;
@@ -207,52 +510,21 @@ fn do_repo_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket:
         println!("Converting to sql");
         let mut inner_index = 1;
 
-        repo_mappings.chunks(1000000).for_each(|chunk| {
-            sql_bytes = group_repo_id_sql_insert(chunk).as_bytes().to_vec();
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_mapping", &repo_mappings, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
 
+        repo_mappings.chunks(1000000).for_each(|chunk| {
             let file_name = format!(
-                "rvh2/{}/{}/{:02}_{:02}.txt.gz",
+                "rvh2/{}/{}/{:02}_{:02}.txt",
                 generate_mode_string(),
                 *YEAR,
                 index,
                 inner_index
             );
             inner_index += 1;
-            println!("compressing and uploading to s3");
-
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            encoder.write_all(&sql_bytes).expect("encoding failed");
-            let compressed_results = encoder.finish().expect("Couldn't compress file, sad.");
-            println!("Compression done.");
-
-            let upload_request = PutObjectRequest {
-                bucket: dest_bucket.clone(),
-                key: file_name.to_owned(),
-                body: Some(compressed_results),
-                ..Default::default()
-            };
-
-            if MODE.dry_run {
-                println!("Not uploading to S3, it's a dry run.  Would have uploaded to bucket {} and key {}.", upload_request.bucket, upload_request.key);
-            } else {
-                println!("Uploading to S3.");
-                // We create a new client every time since the underlying connection pool can
-                // deadlock if all the connections were closed by the receiving end (S3).
-                // This bypasses that issue by creating a new pool every time.
-                let client = S3Client::new(
-                    default_tls_client().expect("Couldn't make TLS client"),
-                    DefaultCredentialsProviderSync::new()
-                        .expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
-                    Region::UsEast1,
-                );
-                match client.put_object(&upload_request) {
-                    Ok(_) => println!(
-                        "uploaded {} to {}",
-                        upload_request.key, upload_request.bucket
-                    ),
-                    Err(_) => println!("Whoops, couldn't upload {}", upload_request.key),
-                }
-            }
+            write_sql(chunk, group_repo_id_sql_insert, file_name, &dest_bucket);
         })
     }
 }
@@ -265,7 +537,6 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
     let mut wrap_things_up = false;
     let mut committer_events: Vec<CommitEvent> = Vec::new();
     let mut sql_collector: Vec<String> = Vec::new();
-    let mut sql_bytes: Vec<u8> = Vec::new();
     let mut index = 0;
 
     loop {
@@ -273,7 +544,6 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
         committer_events.clear();
         let mut should_dedupe = true;
         sql_collector.clear();
-        sql_bytes.clear();
         if wrap_things_up {
             println!("wrapping thread up.");
             break;
@@ -287,10 +557,10 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
                 }
             };
 
-            if should_dedupe && committer_events.len() % dedup_threshold == 0 {
+            // Events can add several committers each, so the size can step over the threshold.
+            if should_dedupe && committer_events.len() >= dedup_threshold {
                 let old_size = committer_events.len();
-                committer_events.sort();
-                committer_events.dedup();
+                dedup_committers(&mut committer_events);
                 println!(
                     "{:?}: Inner loop: we shrunk the committer events from {} to {}",
                     thread::current().id(),
@@ -301,7 +571,7 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
                 // if we've shrunk things to within 1,000,000 or so items of the max item size,
                 // we can call it deduped enough.
                 // Otherwise we spin on this and it's asymptotically closer and closer to the max item size.
-                if dedup_threshold - committer_events.len() < 700000 {
+                if committer_events.len() + 700000 > dedup_threshold {
                     should_dedupe = false;
                 }
             }
@@ -309,17 +579,23 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
                 wrap_things_up = true;
                 break;
             } else {
-                committer_events.push(item.event.as_commit_event());
+                let mut committers = item.event.as_commit_events(MODE.attribute_to_authors);
+                if let Some(bucket) = MODE.committer_bucket {
+                    let period = bucket.period_start(&item.event.created_at);
+                    for committer in committers.iter_mut() {
+                        committer.period = Some(period);
+                    }
+                }
+                committer_events.append(&mut handle_bots(committers, &item));
             }
-            if committer_events.len() == events_to_hold {
+            if committer_events.len() >= events_to_hold {
                 println!("\n\n\nWe got enough work to do!\n\n");
                 break;
             }
         }
 
         let old_size = committer_events.len();
-        committer_events.sort();
-        committer_events.dedup();
+        dedup_committers(&mut committer_events);
         println!(
             "{:?}: We shrunk the committer events from {} to {}",
             thread::current().id(),
@@ -327,233 +603,2036 @@ fn do_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: Stri
             committer_events.len()
         );
 
-        sql_bytes = group_committer_sql_insert_par(&committer_events, OBFUSCATE_COMMITTER_IDS)
-            .as_bytes()
-            .to_vec();
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("committer_repo_id_names", &committer_events, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
 
         let file_name = format!(
-            "rvh2/{}/{}/{:02}.txt.gz",
+            "rvh2/{}/{}/{:02}.txt",
             generate_mode_string(),
             *YEAR,
             index
         );
+        write_sql(
+            &committer_events,
+            |slice| {
+                group_committer_sql_insert_par(
+                    slice,
+                    OBFUSCATE_COMMITTER_IDS,
+                    MODE.bots == BotHandling::Flag,
+                    MODE.committer_bucket.is_some(),
+                )
+            },
+            file_name,
+            &dest_bucket,
+        );
+    }
+}
 
-        // It'd be nice to fire this off to a thread:
-        println!("compressing and uploading to s3");
+/// Mark or remove bot committers, depending on how MODE says to handle bots.
+/// The event rate check only applies to the account that made the event, not PR or commit authors.
+fn handle_bots(mut committers: Vec<CommitEvent>, item: &EventWorkItem) -> Vec<CommitEvent> {
+    if MODE.bots == BotHandling::Keep {
+        return committers;
+    }
+    for committer in committers.iter_mut() {
+        committer.is_bot = BOT_CLASSIFIER.is_bot_login(&committer.actor)
+            || (item.high_rate_actor && Some(&committer.actor) == item.event.actor.login.as_ref());
+    }
+    match MODE.bots {
+        BotHandling::Drop => committers.into_iter().filter(|c| !c.is_bot).collect(),
+        BotHandling::Only => committers.into_iter().filter(|c| c.is_bot).collect(),
+        _ => committers,
+    }
+}
 
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&sql_bytes).expect("encoding failed");
-        let compressed_results = encoder.finish().expect("Couldn't compress file, sad.");
-        println!("Compression done.");
+/// Sort and dedupe committers, per time period if there is one.
+/// If any copy of a committer was flagged as a bot the one we keep is too.
+fn dedup_committers(committers: &mut Vec<CommitEvent>) {
+    committers.sort();
+    committers.dedup_by(|a, b| {
+        if a.actor == b.actor && a.repo_id == b.repo_id && a.period == b.period {
+            b.is_bot = b.is_bot || a.is_bot;
+            return true;
+        }
+        false
+    });
+}
 
-        let upload_request = PutObjectRequest {
-            bucket: dest_bucket.clone(),
-            key: file_name.to_owned(),
-            body: Some(compressed_results),
-            ..Default::default()
-        };
+/// Commit count: every distinct commit SHA pushed to a repo along with its author.
+fn do_commit_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let commits_to_hold = 15000000;
+    let mut wrap_things_up = false;
+    let mut pushed: Vec<(AuthoredCommit, NaiveDate)> = Vec::with_capacity(commits_to_hold);
+    let bucket = MODE.committer_bucket.unwrap_or(TimeBucket::Year);
+    // Counts are kept for the whole run and written once at the end, like the orgs mode's event counts.
+    let mut counter = CommitCounter::new();
+    let mut index = 0;
 
-        {
-            if MODE.dry_run {
-                println!("Not uploading to S3, it's a dry run.  Would have uploaded to bucket {} and key {}.",
-                         upload_request.bucket,
-                         upload_request.key);
-                continue;
-            }
-            let client = S3Client::new(
-                default_tls_client().expect("Couldn't make TLS client"),
-                DefaultCredentialsProviderSync::new()
-                    .expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
-                Region::UsEast1,
-            );
-            println!("Uploading to S3.");
-            // We create a new client every time since the underlying connection pool can
-            // deadlock if all the connections were closed by the receiving end (S3).
-            // This bypasses that issue by creating a new pool every time.
-            match client.put_object(&upload_request) {
-                Ok(_) => println!(
-                    "uploaded {} to {}",
-                    upload_request.key, upload_request.bucket
-                ),
-                Err(_) => {
-                    thread::sleep(time::Duration::from_millis(100));
-                    match client.put_object(&upload_request) {
-                        Ok(_) => println!(
-                            "uploaded {} to {}",
-                            upload_request.key, upload_request.bucket
-                        ),
-                        Err(_) => {
-                            thread::sleep(time::Duration::from_millis(1000));
-                            match client.put_object(&upload_request) {
-                                Ok(_) => println!(
-                                    "uploaded {} to {}",
-                                    upload_request.key, upload_request.bucket
-                                ),
-                                Err(_) => {
-                                    let client = S3Client::new(default_tls_client().expect("Couldn't make TLS client"),
-                                        DefaultCredentialsProviderSync::new().expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
-                                        Region::UsEast1);
-                                    match client.put_object(&upload_request) {
-                                        Ok(_) => println!(
-                                            "uploaded {} to {} with new client",
-                                            upload_request.key, upload_request.bucket
-                                        ),
-                                        Err(e) => println!(
-                                            "FOURTH ATTEMPT TO UPLOAD FAILED SO SAD. {:?}",
-                                            e
-                                        ),
-                                    }
-                                }
-                            };
-                        }
-                    };
-                }
+    while !wrap_things_up {
+        index += 1;
+        pushed.clear();
+        wrap_things_up = fill_batch(&recv, &mut pushed, commits_to_hold, |item| {
+            let period = bucket.period_start(&item.event.created_at);
+            let unlisted = item.event.unlisted_commits();
+            if unlisted > 0 {
+                counter.add_unlisted(item.event.repo.id, period, unlisted);
             }
+            item.event
+                .as_authored_commits()
+                .into_iter()
+                .map(|commit| (commit, period))
+                .collect()
+        });
+
+        // The same commit can be pushed many times: force pushes, pushing to another branch, etc.
+        // It's counted in the period it was first pushed in, and only the first time it's seen this run.
+        let old_size = pushed.len();
+        pushed.sort();
+        pushed.dedup_by(|a, b| a.0.repo_id == b.0.repo_id && a.0.sha == b.0.sha);
+        pushed.retain(|&(ref commit, period)| counter.add(commit, period));
+        println!(
+            "{:?}: We shrunk the commits from {} to {}, {} different commits so far",
+            thread::current().id(),
+            old_size,
+            pushed.len(),
+            counter.len()
+        );
+        let commits: Vec<AuthoredCommit> = pushed.drain(..).map(|(commit, _)| commit).collect();
+
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_commits", &commits, &format!("{:02}", index), &dest_bucket);
+            continue;
         }
-    }
-}
 
-fn generate_mode_string() -> String {
-    if MODE.committer_count {
-        return "committers".to_string();
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(&commits, |slice| group_commit_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS), file_name, &dest_bucket);
     }
-    "repomapping".to_string()
-}
 
-// check things like dryrun etc
-fn environment_check() {
-    let _ = env::var("DESTBUCKET").expect("Need DESTBUCKET set to bucket name");
-    let _ = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
-    let _ = env::var("GHAYEAR").expect("Need GHAYEAR set to year to process");
-    let _ = env::var("GHAHOURS")
-        .expect("Need GHAHOURS set to number of hours (files) to process")
-        .parse::<i64>()
-        .expect("Please set GHAHOURS to an integer value");
+    let commit_counts = counter.commit_counts();
+    println!("{} repo/author commit counts", commit_counts.len());
+    if MODE.output_format != OutputFormat::Sql {
+        output_table("repo_commit_counts", &commit_counts, "stats", &dest_bucket);
+        return;
+    }
+    let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+    write_sql(&commit_counts, |slice| group_commit_count_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS), file_name, &dest_bucket);
 }
 
-/// Make the list of GHA input files.
-fn make_list() -> Vec<String> {
-    let mut file_list = construct_list_of_ingest_files();
-    let mut rng = thread_rng();
-    // Shuffling the list prevents hotspot reads from S3, boosting download performance.
-    rng.shuffle(&mut file_list);
-    println!("file list is now {:#?}", file_list);
-    file_list
-}
+/// Stars: daily star counts and distinct stargazers for each repo.  Every star is kept until the end of the run,
+/// as a hash of the stargazer, so they're only written once.
+fn do_stars_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let stars_to_hold = 15000000;
+    let mut wrap_things_up = false;
+    let mut stars: Vec<StarEvent> = Vec::with_capacity(stars_to_hold);
+    let mut counter = StarCounter::new();
 
-/// Get all events from the file specified on S3
-fn get_event_subset<
-    P: ProvideAwsCredentials + Sync + Send,
-    D: DispatchSignedRequest + Sync + Send,
->(
-    chunk: &[String],
-    client: &S3Client<P, D>,
-) -> Vec<Event> {
-    chunk
-        .par_iter()
-        // todo: don't panic here (issue only when S3 kicks back errors)
-        .flat_map(|file_name| download_and_parse_file(file_name, &client).expect("Issue with file ingest"))
-        .collect()
-}
+    while !wrap_things_up {
+        stars.clear();
+        wrap_things_up = fill_batch(&recv, &mut stars, stars_to_hold, |item| {
+            vec![item.event.as_star_event()]
+        });
+        for star in &stars {
+            counter.add(star);
+        }
+        println!(
+            "{:?}: {} stars, {} different stars so far",
+            thread::current().id(),
+            stars.len(),
+            counter.len()
+        );
+    }
 
-/// Get commit/PR events from the file specified on S3
-fn get_event_subset_committers<
-    P: ProvideAwsCredentials + Sync + Send,
-    D: DispatchSignedRequest + Sync + Send,
->(
-    chunk: &[String],
-    client: &S3Client<P, D>,
-) -> Vec<Event> {
-    let commit_events: Vec<Event> = chunk
-        .par_iter()
-        // todo: don't panic here
-        .flat_map(|file_name| download_and_parse_file(file_name, &client).expect("Issue with file ingest"))
-        .filter(|ref x| x.is_commit_event())
-        .collect();
-    commit_events
+    let daily_stars = counter.daily_stars();
+    let star_stats = counter.star_stats();
+    println!("{} repo days with stars, {} starred repos", daily_stars.len(), star_stats.len());
+    if MODE.output_format != OutputFormat::Sql {
+        output_table("repo_daily_stars", &daily_stars, "stats", &dest_bucket);
+        output_table("repo_star_stats", &star_stats, "stats", &dest_bucket);
+        return;
+    }
+    let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+    let mut file = OutputFile::create(file_name, &dest_bucket);
+    write_sql_slices(&mut file, &daily_stars, group_daily_stars_sql_insert_par);
+    file.write_all(b"\n").expect("Couldn't write SQL");
+    write_sql_slices(&mut file, &star_stats, group_star_stats_sql_insert);
+    file.finish();
 }
 
-/// Struct representing a completed item of work to upload to S3.
-/// Also used as a "no more items" signal.
-#[derive(Debug, Clone)]
-struct WorkItem {
-    sql: String,
-    s3_bucket_name: String,
-    s3_file_location: String,
-    no_more_work: bool,
-}
+/// Forks: edges from parent repo to fork.  With FORK_STATS every edge is kept until the end of the
+/// run so fork counts and depths can be worked out over the whole fork graph.
+fn do_forks_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let forks_to_hold = 15000000;
+    let mut wrap_things_up = false;
+    let mut forks: Vec<ForkEvent> = Vec::with_capacity(forks_to_hold);
+    let mut all_forks: Vec<ForkEvent> = Vec::new();
+    let mut index = 0;
 
-/// Struct for what mode we're in.
-#[derive(Debug, Clone)]
-struct Mode {
-    committer_count: bool,
-    repo_mapping: bool,
-    dry_run: bool,
-}
+    while !wrap_things_up {
+        index += 1;
+        forks.clear();
+        wrap_things_up = fill_batch(&recv, &mut forks, forks_to_hold, |item| {
+            item.event.as_fork_event().into_iter().collect()
+        });
 
-/// Struct representing a file to download and parse.
-/// Also allows a "no more work" signal to be passed.
-#[derive(Debug, Clone)]
-struct FileWorkItem {
-    file: String,
-    no_more_work: bool,
-}
+        let old_size = forks.len();
+        forks.sort();
+        forks.dedup();
+        println!(
+            "{:?}: We shrunk the forks from {} to {}",
+            thread::current().id(),
+            old_size,
+            forks.len()
+        );
+        if MODE.fork_stats {
+            all_forks.extend_from_slice(&forks);
+        }
 
-/// Struct representing a 2015 and later event.
-/// Also allows a "no more work" signal to be passed.
-#[derive(Debug, Clone)]
-struct EventWorkItem {
-    event: Event,
-    no_more_work: bool,
-}
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_forks", &forks, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
 
-lazy_static! {
-    static ref YEAR: i32 = {
-        env::var("GHAYEAR").expect("Please set GHAYEAR env var").parse::<i32>().expect("Please set GHAYEAR env var to an integer value.")
-    };
-}
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(&forks, |slice| group_forks_sql_insert_par(slice), file_name, &dest_bucket);
+    }
 
-// if a repo ID shows up twice the collection we received has a duplicate in it
-fn dupes_in(repo_id_mappings: &[RepoIdToName]) -> bool {
-    let mut repo_ids = repo_id_mappings
-        .iter()
-        .map(|item| item.repo_id)
-        .collect::<Vec<i64>>();
-    let old_count = repo_ids.len();
-    repo_ids.sort();
-    repo_ids.dedup();
-    if old_count != repo_ids.len() {
-        return true;
+    if MODE.fork_stats {
+        println!("Working out fork stats for {} forks", all_forks.len());
+        let stats = fork_stats(&all_forks);
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_fork_stats", &stats, "stats", &dest_bucket);
+            return;
+        }
+        let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+        compress_and_upload(group_fork_stats_sql_insert(&stats).as_bytes(), file_name, &dest_bucket);
     }
-    false
 }
 
-// Since we're doing nothing on conflict, we don't need to separate out any duplicates we may have received.
-fn group_committer_sql_insert_par(committers: &[CommitEvent], obfuscate: bool) -> String {
-    committers
-        .par_chunks(20)
-        .map(|chunk| {
-            let row_to_insert: String = chunk
-                .iter()
-                .map(|chunk| {
-                    let actor_name = match obfuscate {
-                        true => {
-                            let mut sha_er = sha1::Sha1::new();
-                            sha_er.update(chunk.actor.as_bytes());
-                            sha_er.digest().to_string()
-                        }
-                        false => chunk.actor.clone(),
-                    };
-                    format!("({}, '{}')", chunk.repo_id, actor_name)
-                })
-                .collect::<Vec<String>>()
-                .join(", ");
+/// Pull requests: one row per PR with the newest state we've seen for it.  With PR_STATS merged
+/// PRs are kept until the end of the run to work out time to merge stats for each repo.
+fn do_pr_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let prs_to_hold = 10000000;
+    let mut wrap_things_up = false;
+    let mut prs: Vec<PrLifecycle> = Vec::with_capacity(prs_to_hold);
+    let mut merged_prs: Vec<PrLifecycle> = Vec::new();
+    let mut index = 0;
 
-            format!("INSERT INTO committer_repo_id_names (repo_id, actor_name) VALUES {} ON CONFLICT DO NOTHING;", row_to_insert)
-        })
-        .collect::<Vec<String>>()
-        .join("\n")
-}
+    while !wrap_things_up {
+        index += 1;
+        prs.clear();
+        wrap_things_up = fill_batch(&recv, &mut prs, prs_to_hold, |item| {
+            item.event.as_pr_lifecycle().into_iter().collect()
+        });
+
+        // Newest event for each PR first so that's the one dedupe keeps.
+        let old_size = prs.len();
+        prs.sort_by(|a, b| (a.repo_id, a.number, b.updated_at).cmp(&(b.repo_id, b.number, a.updated_at)));
+        prs.dedup_by(|a, b| a.repo_id == b.repo_id && a.number == b.number);
+        println!(
+            "{:?}: We shrunk the pull requests from {} to {}",
+            thread::current().id(),
+            old_size,
+            prs.len()
+        );
+        if MODE.pr_stats {
+            merged_prs.extend(prs.iter().filter(|pr| pr.merged_at.is_some()).cloned());
+        }
+
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("pull_requests", &prs, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(&prs, |slice| group_pr_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS), file_name, &dest_bucket);
+    }
+
+    if MODE.pr_stats {
+        println!("Working out time to merge for {} merged pull requests", merged_prs.len());
+        let stats = merge_stats(&merged_prs);
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_merge_stats", &stats, "stats", &dest_bucket);
+            return;
+        }
+        let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+        compress_and_upload(group_merge_stats_sql_insert(&stats).as_bytes(), file_name, &dest_bucket);
+    }
+}
+
+/// Issues: every open, close, reopen, label change and comment on issues and PRs.
+/// With ISSUE_STATS a summary of each issue is kept for the run to work out how responsive each repo is.
+fn do_issues_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let activities_to_hold = 10000000;
+    let mut wrap_things_up = false;
+    let mut activities: Vec<IssueActivity> = Vec::with_capacity(activities_to_hold);
+    let mut collector = IssueStatsCollector::new();
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        activities.clear();
+        wrap_things_up = fill_batch(&recv, &mut activities, activities_to_hold, |item| {
+            item.event.as_issue_activity().into_iter().collect()
+        });
+
+        let old_size = activities.len();
+        activities.sort();
+        activities.dedup_by(|a, b| a.event_id == b.event_id);
+        println!(
+            "{:?}: We shrunk the issue activity from {} to {}",
+            thread::current().id(),
+            old_size,
+            activities.len()
+        );
+        if MODE.issue_stats {
+            for activity in &activities {
+                collector.add(activity);
+            }
+        }
+
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("issue_events", &activities, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(&activities, |slice| group_issue_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS), file_name, &dest_bucket);
+    }
+
+    if MODE.issue_stats {
+        let stats = collector.stats();
+        println!("Working out issue stats for {} repos", stats.len());
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_issue_stats", &stats, "stats", &dest_bucket);
+            return;
+        }
+        let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+        compress_and_upload(group_issue_stats_sql_insert(&stats).as_bytes(), file_name, &dest_bucket);
+    }
+}
+
+/// First contributions: the earliest commit or accepted PR for each committer in each repo.  A PR counts from when it
+/// was opened.  Committers are found the same way as the committer count, including ATTRIBUTE_TO_AUTHORS and BOTS.
+fn do_first_contribution_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let contributions_to_hold = 15000000;
+    let mut wrap_things_up = false;
+    let mut contributions: Vec<FirstContribution> = Vec::with_capacity(contributions_to_hold);
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        contributions.clear();
+        wrap_things_up = fill_batch(&recv, &mut contributions, contributions_to_hold, |item| {
+            let committers = item.event.as_commit_events(MODE.attribute_to_authors);
+            handle_bots(committers, item)
+                .into_iter()
+                .map(|committer| FirstContribution {
+                    repo_id: committer.repo_id,
+                    actor: committer.actor,
+                    first_contributed_at: item.event.contributed_at(),
+                    is_bot: committer.is_bot,
+                })
+                .collect()
+        });
+
+        // Sorted by time within each committer and repo so the dedupe keeps the earliest, flagged as a bot if
+        // any of them were.
+        let old_size = contributions.len();
+        contributions.sort();
+        contributions.dedup_by(|a, b| {
+            if a.repo_id == b.repo_id && a.actor == b.actor {
+                b.is_bot = b.is_bot || a.is_bot;
+                return true;
+            }
+            false
+        });
+        println!(
+            "{:?}: We shrunk the first contributions from {} to {}",
+            thread::current().id(),
+            old_size,
+            contributions.len()
+        );
+
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("first_contributions", &contributions, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(
+            &contributions,
+            |slice| {
+                group_first_contribution_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS, MODE.bots == BotHandling::Flag)
+            },
+            file_name,
+            &dest_bucket,
+        );
+    }
+}
+
+/// Releases: tags and branches being created and deleted, and releases being published, for per repo timelines.
+/// With RELEASE_STATS tags and releases are kept until the end of the run to work out each repo's release cadence.
+fn do_releases_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let activities_to_hold = 10000000;
+    let mut wrap_things_up = false;
+    let mut activities: Vec<ReleaseActivity> = Vec::with_capacity(activities_to_hold);
+    let mut all_releases: Vec<ReleaseActivity> = Vec::new();
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        activities.clear();
+        wrap_things_up = fill_batch(&recv, &mut activities, activities_to_hold, |item| {
+            item.event.as_release_activity().into_iter().collect()
+        });
+
+        let old_size = activities.len();
+        activities.sort();
+        activities.dedup_by(|a, b| a.event_id == b.event_id);
+        println!(
+            "{:?}: We shrunk the release activity from {} to {}",
+            thread::current().id(),
+            old_size,
+            activities.len()
+        );
+        if MODE.release_stats {
+            all_releases.extend(activities.iter().filter(|activity| activity.kind != "branch").cloned());
+        }
+
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_releases", &activities, &format!("{:02}", index), &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        write_sql(&activities, |slice| group_release_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS), file_name, &dest_bucket);
+    }
+
+    if MODE.release_stats {
+        println!("Working out release cadence for {} tags and releases", all_releases.len());
+        let stats = release_cadence(&all_releases);
+        if MODE.output_format != OutputFormat::Sql {
+            output_table("repo_release_stats", &stats, "stats", &dest_bucket);
+            return;
+        }
+        let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+        compress_and_upload(group_release_cadence_sql_insert(&stats).as_bytes(), file_name, &dest_bucket);
+    }
+}
+
+/// Reviewers: every review and review comment on pull requests, plus the distinct reviewers for each repo.
+/// Reviewers are obfuscated and BOTS applies like it does for committers.
+fn do_reviewers_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let reviews_to_hold = 10000000;
+    let mut wrap_things_up = false;
+    let mut reviews: Vec<PrReview> = Vec::with_capacity(reviews_to_hold);
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        reviews.clear();
+        wrap_things_up = fill_batch(&recv, &mut reviews, reviews_to_hold, |item| {
+            let review = match item.event.as_pr_review() {
+                Some(review) => review,
+                None => return Vec::new(),
+            };
+            let reviewer = CommitEvent {
+                actor: review.reviewer.clone(),
+                repo_id: review.repo_id,
+                period: None,
+                is_bot: false,
+            };
+            handle_bots(vec![reviewer], item)
+                .into_iter()
+                .map(|reviewer| PrReview {
+                    is_bot: reviewer.is_bot,
+                    ..review.clone()
+                })
+                .collect()
+        });
+
+        let old_size = reviews.len();
+        reviews.sort();
+        reviews.dedup_by(|a, b| a.event_id == b.event_id);
+        let mut reviewers: Vec<CommitEvent> = reviews
+            .iter()
+            .map(|review| CommitEvent {
+                actor: review.reviewer.clone(),
+                repo_id: review.repo_id,
+                period: None,
+                is_bot: review.is_bot,
+            })
+            .collect();
+        dedup_committers(&mut reviewers);
+        println!(
+            "{:?}: We shrunk the reviews from {} to {}, by {} reviewers",
+            thread::current().id(),
+            old_size,
+            reviews.len(),
+            reviewers.len()
+        );
+
+        if MODE.output_format != OutputFormat::Sql {
+            let batch = format!("{:02}", index);
+            output_table("pr_reviews", &reviews, &batch, &dest_bucket);
+            output_table("reviewer_repo_id_names", &reviewers, &batch, &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        let mut file = OutputFile::create(file_name, &dest_bucket);
+        write_sql_slices(&mut file, &reviews, |slice| {
+            group_review_sql_insert_par(slice, OBFUSCATE_COMMITTER_IDS)
+        });
+        file.write_all(b"\n").expect("Couldn't write SQL");
+        write_sql_slices(&mut file, &reviewers, |slice| {
+            group_repo_actor_sql_insert_par(
+                "reviewer_repo_id_names",
+                slice,
+                OBFUSCATE_COMMITTER_IDS,
+                MODE.bots == BotHandling::Flag,
+                false,
+            )
+        });
+        file.finish();
+    }
+}
+
+/// Histogram: events per type and hour and how many repos and actors there were, for sanity checks.
+/// Nothing's held but the counts, and there's no SQL: the summary is uploaded as JSON and CSV at the end of the run.
+fn do_histogram_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let mut histogram = EventHistogram::new();
+    loop {
+        let item: EventWorkItem = match recv.recv() {
+            Ok(i) => i,
+            Err(_) => {
+                panic!("receiving error");
+            }
+        };
+        if item.no_more_work {
+            break;
+        }
+        histogram.add(&item.event);
+    }
+
+    let summary = histogram.summary();
+    println!(
+        "{} events from {} repos and {} actors: {:?}",
+        summary.events, summary.distinct_repos, summary.distinct_actors, summary.event_types
+    );
+    let json = serde_json::to_string_pretty(&summary).expect("Couldn't make JSON of the histogram");
+    let file_name = format!("rvh2/{}/{}/summary.json", generate_mode_string(), *YEAR);
+    compress_and_upload(json.as_bytes(), file_name, &dest_bucket);
+    let file_name = format!("rvh2/{}/{}/summary.csv", generate_mode_string(), *YEAR);
+    compress_and_upload(histogram_csv(&summary).as_bytes(), file_name, &dest_bucket);
+}
+
+/// Subset: the raw JSON of the chosen repos' events, gzipped JSON lines in a directory per repo.
+fn do_subset_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    do_raw_lines_work_son(recv, dest_bucket, |event| event.repo.id.to_string())
+}
+
+/// Repartition: the raw JSON of every event, gzipped JSON lines in a directory per repo shard or day.
+fn do_repartition_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    do_raw_lines_work_son(recv, dest_bucket, |event| PARTITIONING.partition(event))
+}
+
+/// Pass events on as the JSON they came in as, or as flattened events in Parquet, CSV or SQLite, split up by `partition`.
+/// Each batch makes a new file for each partition it has events for.
+fn do_raw_lines_work_son<F>(
+    recv: std::sync::mpsc::Receiver<EventWorkItem>,
+    dest_bucket: String,
+    partition: F,
+) where
+    F: Fn(&Event) -> String,
+{
+    let lines_to_hold = 1000000;
+    let mut wrap_things_up = false;
+    let mut lines: Vec<(String, PassedOn)> = Vec::with_capacity(lines_to_hold);
+    let as_json = MODE.output_format == OutputFormat::Sql || MODE.output_format == OutputFormat::Jsonl;
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        lines.clear();
+        wrap_things_up = fill_batch(&recv, &mut lines, lines_to_hold, |item| match item.raw_line {
+            Some(ref line) => vec![(
+                partition(&item.event),
+                if as_json {
+                    PassedOn::Line(line.clone())
+                } else {
+                    PassedOn::Flattened(item.event.clone())
+                },
+            )],
+            None => Vec::new(),
+        });
+
+        // Stable sort, so each partition's events stay in the order they came in.
+        lines.sort_by(|a, b| a.0.cmp(&b.0));
+        let partitions = split_by_partition(&lines);
+        println!(
+            "{:?}: {} events for {} partitions",
+            thread::current().id(),
+            lines.len(),
+            partitions.len()
+        );
+        partitions.par_iter().for_each(|partition_lines| {
+            let dir = format!("rvh2/{}/{}/{}", generate_mode_string(), *YEAR, partition_lines[0].0);
+            match MODE.output_format {
+                // Raw events have no SQL, so the default writes the archive's JSON lines as they are, like jsonl.
+                OutputFormat::Sql | OutputFormat::Jsonl => {
+                    let mut file = OutputFile::create(format!("{}/{:02}.jsonl", dir, index), &dest_bucket);
+                    for &(_, ref passed_on) in partition_lines.iter() {
+                        if let PassedOn::Line(ref line) = *passed_on {
+                            writeln!(file, "{}", line).expect("Couldn't write events");
+                        }
+                    }
+                    file.finish();
+                }
+                OutputFormat::Parquet => {
+                    let rows = flattened_rows(partition_lines);
+                    let file = parquet_table(&Event::columns(), &rows, *PARQUET_ROW_GROUP_SIZE);
+                    upload(file, format!("{}/{:02}.parquet", dir, index), &dest_bucket);
+                }
+                OutputFormat::Csv => {
+                    let rows = flattened_rows(partition_lines);
+                    write_text_file(&Event::columns(), &rows, format!("{}/{:02}", dir, index), &dest_bucket);
+                }
+                OutputFormat::Sqlite => {
+                    let rows = flattened_rows(partition_lines);
+                    SQLITE_SINK
+                        .lock()
+                        .expect("Couldn't lock the SQLite database")
+                        .insert("events", &Event::columns(), &rows)
+                        .expect("Couldn't write events to SQLite");
+                }
+            }
+        });
+    }
+}
+
+/// What the raw lines modes keep of an event: the line of JSON for JSON output, the event for everything else.
+enum PassedOn {
+    Line(String),
+    Flattened(Event),
+}
+
+fn flattened_rows(partition_lines: &[(String, PassedOn)]) -> Vec<Vec<Value>> {
+    partition_lines
+        .iter()
+        .filter_map(|&(_, ref passed_on)| match *passed_on {
+            PassedOn::Flattened(ref event) => Some(event.values()),
+            PassedOn::Line(_) => None,
+        })
+        .collect()
+}
+
+/// Split lines sorted by partition into a slice for each partition.
+fn split_by_partition<T>(lines: &[(String, T)]) -> Vec<&[(String, T)]> {
+    let mut partitions: Vec<&[(String, T)]> = Vec::new();
+    let mut start = 0;
+    for end in 1..lines.len() + 1 {
+        if end == lines.len() || lines[end].0 != lines[start].0 {
+            partitions.push(&lines[start..end]);
+            start = end;
+        }
+    }
+    partitions
+}
+
+/// Org rollups: events per org and event type, plus the distinct repos and committers for each org.
+/// Event counts from each batch are added to what's already loaded, repos and committers are deduped on load.
+fn do_org_work_son(recv: std::sync::mpsc::Receiver<EventWorkItem>, dest_bucket: String) {
+    let activities_to_hold = 15000000;
+    let mut wrap_things_up = false;
+    let mut activities: Vec<OrgActivity> = Vec::with_capacity(activities_to_hold);
+    // Event counts are kept for the whole run and written once at the end, so loading them again replaces them
+    // instead of counting the same events twice.
+    let mut run_event_counts: HashMap<(String, String), usize> = HashMap::new();
+    let mut index = 0;
+
+    while !wrap_things_up {
+        index += 1;
+        activities.clear();
+        wrap_things_up = fill_batch(&recv, &mut activities, activities_to_hold, |item| {
+            let mut activity = item.event.as_org_activity(MODE.attribute_to_authors);
+            if MODE.bots != BotHandling::Keep {
+                let committers = activity
+                    .committers
+                    .iter()
+                    .map(|&(ref actor, is_bot)| CommitEvent {
+                        actor: actor.clone(),
+                        repo_id: activity.repo_id,
+                        period: None,
+                        is_bot: is_bot,
+                    })
+                    .collect();
+                activity.committers = handle_bots(committers, item)
+                    .into_iter()
+                    .map(|committer| (committer.actor, committer.is_bot))
+                    .collect();
+            }
+            vec![activity]
+        });
+
+        let mut event_counts: HashMap<(&str, &str), usize> = HashMap::new();
+        let mut repos: Vec<(&str, i64)> = Vec::new();
+        let mut committers: Vec<(&str, &str, bool)> = Vec::new();
+        for activity in &activities {
+            *event_counts
+                .entry((&activity.org, &activity.event_type))
+                .or_insert(0) += 1;
+            repos.push((&activity.org, activity.repo_id));
+            for &(ref committer, is_bot) in &activity.committers {
+                committers.push((&activity.org, committer, is_bot));
+            }
+        }
+        for ((org, event_type), events) in event_counts {
+            *run_event_counts
+                .entry((org.to_string(), event_type.to_string()))
+                .or_insert(0) += events;
+        }
+        repos.sort();
+        repos.dedup();
+        committers.sort();
+        committers.dedup_by(|a, b| {
+            if a.0 == b.0 && a.1 == b.1 {
+                b.2 = b.2 || a.2;
+                return true;
+            }
+            false
+        });
+        println!(
+            "{:?}: {} events, {} org repos and {} org committers",
+            thread::current().id(),
+            activities.len(),
+            repos.len(),
+            committers.len()
+        );
+
+        if MODE.output_format != OutputFormat::Sql {
+            let batch = format!("{:02}", index);
+            output_table("org_repos", &repos, &batch, &dest_bucket);
+            output_table("org_committers", &committers, &batch, &dest_bucket);
+            continue;
+        }
+
+        let file_name = format!(
+            "rvh2/{}/{}/{:02}.txt",
+            generate_mode_string(),
+            *YEAR,
+            index
+        );
+        let mut file = OutputFile::create(file_name, &dest_bucket);
+        write_sql_slices(&mut file, &repos, group_org_repos_sql_insert);
+        file.write_all(b"\n").expect("Couldn't write SQL");
+        write_sql_slices(&mut file, &committers, |slice| {
+            group_org_committers_sql_insert(slice, OBFUSCATE_COMMITTER_IDS, MODE.bots == BotHandling::Flag)
+        });
+        file.finish();
+    }
+
+    let mut event_counts: Vec<OrgEventCount> = run_event_counts
+        .into_iter()
+        .map(|((org, event_type), events)| OrgEventCount {
+            org: org,
+            event_type: event_type,
+            events: events,
+        })
+        .collect();
+    event_counts.sort();
+    println!("{} org/event type counts", event_counts.len());
+    if MODE.output_format != OutputFormat::Sql {
+        output_table("org_event_counts", &event_counts, "stats", &dest_bucket);
+        return;
+    }
+    let file_name = format!("rvh2/{}/{}/stats.txt", generate_mode_string(), *YEAR);
+    compress_and_upload(group_org_event_counts_sql_insert(&event_counts).as_bytes(), file_name, &dest_bucket);
+}
+
+/// Take work items off the channel, converting them with `convert`, until there's at least
+/// `batch_size` items in the batch.  Returns true if the senders said there's no more work.
+fn fill_batch<T, F>(
+    recv: &std::sync::mpsc::Receiver<EventWorkItem>,
+    batch: &mut Vec<T>,
+    batch_size: usize,
+    mut convert: F,
+) -> bool
+where
+    F: FnMut(&EventWorkItem) -> Vec<T>,
+{
+    loop {
+        let item: EventWorkItem = match recv.recv() {
+            Ok(i) => i,
+            Err(_) => {
+                panic!("receiving error");
+            }
+        };
+        if item.no_more_work {
+            return true;
+        }
+        batch.append(&mut convert(&item));
+        if batch.len() >= batch_size {
+            println!("\n\n\nWe got enough work to do!\n\n");
+            return false;
+        }
+    }
+}
+
+/// Write the SQL for the records to an output file a slice of records at a time, so only one slice's SQL
+/// is in memory instead of the whole batch's.
+fn write_sql<T, F>(records: &[T], to_sql: F, file_name: String, dest_bucket: &str)
+where
+    F: Fn(&[T]) -> String,
+{
+    let mut file = OutputFile::create(file_name, dest_bucket);
+    write_sql_slices(&mut file, records, to_sql);
+    file.finish();
+}
+
+/// Write the SQL for each slice of SQL_SLICE_RECORDS records, a line apart.  The slices are a multiple of the
+/// statement sizes, so it's the same SQL as making it for all the records at once.
+fn write_sql_slices<T, F, W>(out: &mut W, records: &[T], to_sql: F)
+where
+    F: Fn(&[T]) -> String,
+    W: Write,
+{
+    for (index, slice) in records.chunks(SQL_SLICE_RECORDS).enumerate() {
+        if index > 0 {
+            out.write_all(b"\n").expect("Couldn't write SQL");
+        }
+        out.write_all(to_sql(slice).as_bytes()).expect("Couldn't write SQL");
+    }
+}
+
+/// Write a batch of a table's records as OUTPUT_FORMAT, in `rvh2/<mode>/<year>/<table>/` or the SQLite database.
+/// Actor columns are obfuscated like committers are.  Parquet batches are split into files of PARQUET_FILE_ROWS rows
+/// and JSON lines get a `schema.json` next to them, written with the table's first batch.
+fn output_table<R: Record + Sync>(table: &str, records: &[R], batch: &str, dest_bucket: &str) {
+    let columns = R::columns();
+    let rows: Vec<Vec<Value>> = records
+        .par_iter()
+        .map(|record| obfuscate_actors(&columns, record.values()))
+        .collect();
+    let dir = format!("rvh2/{}/{}/{}", generate_mode_string(), *YEAR, table);
+    match MODE.output_format {
+        OutputFormat::Sqlite => SQLITE_SINK
+            .lock()
+            .expect("Couldn't lock the SQLite database")
+            .insert(table, &columns, &rows)
+            .expect("Couldn't write to SQLite"),
+        OutputFormat::Parquet => for (file_index, file_rows) in rows.chunks(*PARQUET_FILE_ROWS).enumerate() {
+            let file = parquet_table(&columns, file_rows, *PARQUET_ROW_GROUP_SIZE);
+            upload(file, format!("{}/{}_{:02}.parquet", dir, batch, file_index + 1), dest_bucket);
+        },
+        _ => {
+            if MODE.output_format == OutputFormat::Jsonl
+                && SCHEMAS_WRITTEN
+                    .lock()
+                    .expect("Couldn't lock the written schemas")
+                    .insert(dir.clone())
+            {
+                upload(json_schema(&columns).into_bytes(), format!("{}/schema.json", dir), dest_bucket);
+            }
+            write_text_file(&columns, &rows, format!("{}/{}", dir, batch), dest_bucket);
+        }
+    }
+}
+
+/// Write the rows as CSV or JSON lines to `file_name` with the format's extension added.
+fn write_text_file(columns: &[Column], rows: &[Vec<Value>], file_name: String, dest_bucket: &str) {
+    let extension = match MODE.output_format {
+        OutputFormat::Csv => "csv",
+        OutputFormat::Jsonl => "jsonl",
+        format => panic!("{:?} output isn't text", format),
+    };
+    let mut file = OutputFile::create(format!("{}.{}", file_name, extension), dest_bucket);
+    match MODE.output_format {
+        OutputFormat::Csv => write_csv(&mut file, columns, rows),
+        _ => write_json_lines(&mut file, columns, rows),
+    }.expect("Couldn't write rows");
+    file.finish();
+}
+
+fn obfuscate_actors(columns: &[Column], mut values: Vec<Value>) -> Vec<Value> {
+    if !OBFUSCATE_COMMITTER_IDS {
+        return values;
+    }
+    for (column, value) in columns.iter().zip(values.iter_mut()) {
+        if let Value::Utf8(ref mut name) = *value {
+            if column.actor {
+                *name = obfuscate_id(name);
+            }
+        }
+    }
+    values
+}
+
+/// Write the bytes to a local file, making its directory if needed.
+fn write_file(bytes: &[u8], path: &Path) {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("Couldn't make output directory");
+    }
+    File::create(path)
+        .and_then(|mut file| file.write_all(bytes))
+        .expect("Couldn't write output file");
+    println!("Wrote {}", path.display());
+}
+
+/// Compress the bytes with OUTPUT_COMPRESSION and upload them to the destination bucket or write them under OUTPUT_DIR.
+fn compress_and_upload(bytes: &[u8], file_name: String, dest_bucket: &str) {
+    let mut file = OutputFile::create(file_name, dest_bucket);
+    file.write_all(bytes).expect("Couldn't compress file");
+    file.finish();
+}
+
+/// Where an output file's compressed bytes go while it's written: straight to its file under OUTPUT_DIR,
+/// or into memory in UPLOAD_PART_SIZE parts to be queued for upload once it's done.
+enum Destination {
+    File(ChecksumWriter<BufWriter<File>>, PathBuf),
+    Memory(ChecksumWriter<PartWriter>),
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Destination::File(ref mut file, _) => file.write(buf),
+            Destination::Memory(ref mut parts) => parts.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Destination::File(ref mut file, _) => file.flush(),
+            Destination::Memory(_) => Ok(()),
+        }
+    }
+}
+
+/// An output file that's compressed with OUTPUT_COMPRESSION as it's written, so the uncompressed file is never
+/// all in memory.  The codec's extension is added to the name.  It isn't uploaded until `finish` is called.
+struct OutputFile {
+    file_name: String,
+    dest_bucket: String,
+    encoder: Encoder<Destination>,
+}
+
+impl OutputFile {
+    fn create(file_name: String, dest_bucket: &str) -> OutputFile {
+        let file_name = format!("{}{}", file_name, MODE.output_codec.extension());
+        let destination = match *OUTPUT_DIR {
+            Some(ref output_dir) => {
+                let path = output_dir.join(&file_name);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).expect("Couldn't make output directory");
+                }
+                let file = File::create(&path).expect("Couldn't create output file");
+                Destination::File(ChecksumWriter::new(BufWriter::new(file)), path)
+            }
+            None => Destination::Memory(ChecksumWriter::new(PartWriter::new(UPLOAD_PART_SIZE))),
+        };
+        OutputFile {
+            file_name: file_name,
+            dest_bucket: dest_bucket.to_owned(),
+            encoder: MODE.output_codec
+                .encoder(destination)
+                .expect("Couldn't start compressing"),
+        }
+    }
+
+    /// Finish compressing and upload the file, or close it if it's local.  Its checksums go in the run's manifest.
+    fn finish(self) {
+        match self.encoder.finish().expect("Couldn't finish compressing") {
+            Destination::File(file, path) => {
+                let (mut file, checksums) = file.finish();
+                file.flush().expect("Couldn't write output file");
+                MANIFEST
+                    .lock()
+                    .expect("Couldn't lock the manifest")
+                    .add(&self.file_name, checksums);
+                println!("Wrote {}", path.display());
+            }
+            Destination::Memory(parts) => {
+                let (parts, checksums) = parts.finish();
+                upload_parts(parts.into_parts(), checksums, self.file_name, &self.dest_bucket);
+            }
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Upload the file to the destination bucket as is, retrying a few times if S3 kicks back errors.
+/// With OUTPUT_DIR set it's written to the same path under that directory instead, even on a dry run.
+/// Either way its checksums go in the run's manifest.
+fn upload(body: Vec<u8>, file_name: String, dest_bucket: &str) {
+    let checksums = Checksums::of(&body);
+    if let Some(ref output_dir) = *OUTPUT_DIR {
+        write_file(&body, &output_dir.join(&file_name));
+        MANIFEST
+            .lock()
+            .expect("Couldn't lock the manifest")
+            .add(&file_name, checksums);
+        return;
+    }
+    upload_parts(into_parts(body, UPLOAD_PART_SIZE), checksums, file_name, dest_bucket);
+}
+
+/// Queue a file that's in UPLOAD_PART_SIZE parts for upload and put its checksums in the run's manifest.
+fn upload_parts(parts: Vec<Vec<u8>>, checksums: Checksums, file_name: String, dest_bucket: &str) {
+    MANIFEST
+        .lock()
+        .expect("Couldn't lock the manifest")
+        .add(&file_name, checksums);
+    if MODE.dry_run {
+        println!("Not uploading to S3, it's a dry run.  Would have uploaded to bucket {} and key {}.",
+                 dest_bucket,
+                 file_name);
+        return;
+    }
+    let upload = Upload {
+        bucket: dest_bucket.to_owned(),
+        key: file_name,
+        parts: parts,
+    };
+    if let Err(e) = UPLOADER.send(upload) {
+        panic!("Giving up, uploading results failed: {}", e);
+    }
+}
+
+/// Write the run's manifest to `rvh2/<mode>/<year>/manifests/<run>.json`, once everything in it is uploaded.
+fn write_manifest(dest_bucket: &str) {
+    let manifest = MANIFEST.lock().expect("Couldn't lock the manifest");
+    if manifest.files.is_empty() {
+        return;
+    }
+    let json = serde_json::to_vec_pretty(&*manifest).expect("Couldn't make JSON of the manifest");
+    let file_name = format!("rvh2/{}/{}/manifests/{}.json", generate_mode_string(), *YEAR, *RUN_ID);
+    if let Some(ref output_dir) = *OUTPUT_DIR {
+        write_file(&json, &output_dir.join(file_name));
+        return;
+    }
+    if MODE.dry_run {
+        println!("Not uploading the manifest, it's a dry run.  Would have uploaded to {}.", file_name);
+        return;
+    }
+    let upload = Upload {
+        bucket: dest_bucket.to_owned(),
+        key: file_name,
+        parts: vec![json],
+    };
+    if let Err(e) = put_with_retries(upload) {
+        panic!("Uploading the manifest failed: {}", e);
+    }
+}
+
+/// Check every file in the run's manifest at VERIFY_MANIFEST is in the destination bucket, or under OUTPUT_DIR,
+/// with the size and checksums it was written with.  Fails if any aren't.
+fn verify_output() {
+    let manifest_key = env::var("VERIFY_MANIFEST").expect("Need VERIFY_MANIFEST set to the key of a run's manifest");
+    if OUTPUT_DIR.is_none() {
+        let _ = env::var("DESTBUCKET").expect("Need DESTBUCKET or OUTPUT_DIR set");
+    }
+    let dest_bucket = env::var("DESTBUCKET").unwrap_or(String::new());
+    let manifest_json = read_output_object(&manifest_key, &dest_bucket).expect("Couldn't get the manifest");
+    let manifest: Manifest = serde_json::from_slice(&manifest_json).expect("Couldn't read the manifest");
+    println!("Verifying {} files from run {}", manifest.files.len(), manifest.run);
+
+    let failures: Vec<String> = manifest
+        .files
+        .par_iter()
+        .filter_map(|(key, checksums)| {
+            let checked = read_output_object(key, &dest_bucket).and_then(|bytes| checksums.verify(&bytes));
+            match checked {
+                Ok(_) => None,
+                Err(e) => Some(format!("{}: {}", key, e)),
+            }
+        })
+        .collect();
+    for failure in &failures {
+        println!("Bad file {}", failure);
+    }
+    if !failures.is_empty() {
+        panic!("{} of {} files failed verification", failures.len(), manifest.files.len());
+    }
+    println!("All {} files verified", manifest.files.len());
+}
+
+/// Get a file of output from under OUTPUT_DIR if it's set, or the destination bucket.
+fn read_output_object(key: &str, dest_bucket: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    if let Some(ref output_dir) = *OUTPUT_DIR {
+        File::open(output_dir.join(key))
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| format!("Couldn't read it: {}", e))?;
+        return Ok(bytes);
+    }
+    let client = S3Client::new(
+        default_tls_client().expect("Couldn't make TLS client"),
+        DefaultCredentialsProviderSync::new().expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
+        Region::UsEast1,
+    );
+    let object = get_object_with_retries(dest_bucket, key, &client)?;
+    object
+        .body
+        .ok_or_else(|| "S3 sent no body".to_string())?
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Couldn't download it: {}", e))?;
+    Ok(bytes)
+}
+
+/// Put a file in S3, as a multipart upload if it has more than one part.  Tries four times before giving up.
+/// Content-MD5 is sent with the file or each part so S3 turns down anything that didn't arrive whole.
+fn put_with_retries(upload: Upload) -> Result<(), String> {
+    let Upload { bucket, key, mut parts } = upload;
+    // The requests are made once and sent again on each attempt, so the file's bytes are never copied.
+    let whole = match parts.len() {
+        0 | 1 => {
+            let body = parts.pop().unwrap_or_default();
+            Some(PutObjectRequest {
+                bucket: bucket.clone(),
+                key: key.clone(),
+                content_md5: Some(content_md5(&body)),
+                body: Some(body),
+                ..Default::default()
+            })
+        }
+        _ => None,
+    };
+    let mut part_requests: Vec<UploadPartRequest> = parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, part)| UploadPartRequest {
+            bucket: bucket.clone(),
+            key: key.clone(),
+            part_number: index as i64 + 1,
+            content_md5: Some(content_md5(&part)),
+            body: Some(part),
+            ..Default::default()
+        })
+        .collect();
+
+    let mut last_error = String::new();
+    for attempt in 0..4 {
+        if attempt > 0 {
+            thread::sleep(time::Duration::from_millis(100 * 10u64.pow(attempt - 1)));
+        }
+        // We create a new client every time since the underlying connection pool can
+        // deadlock if all the connections were closed by the receiving end (S3).
+        // This bypasses that issue by creating a new pool every time.
+        let client = S3Client::new(
+            default_tls_client().expect("Couldn't make TLS client"),
+            DefaultCredentialsProviderSync::new()
+                .expect("Couldn't get new copy of DefaultCredentialsProviderSync"),
+            Region::UsEast1,
+        );
+        let result = match whole {
+            Some(ref request) => client
+                .put_object(request)
+                .map(|_| ())
+                .map_err(|e| format!("{:?}", e)),
+            None => put_multipart(&client, &bucket, &key, &mut part_requests),
+        };
+        match result {
+            Ok(_) => {
+                println!("uploaded {} to {}", key, bucket);
+                return Ok(());
+            }
+            Err(e) => {
+                println!("Attempt {} to upload {} failed: {}", attempt + 1, key, e);
+                last_error = e;
+            }
+        }
+    }
+    Err(format!("couldn't upload {} to {}: {}", key, bucket, last_error))
+}
+
+/// Upload the parts as a multipart upload, aborting it if a part fails so S3 doesn't keep them.
+fn put_multipart<P, D>(
+    client: &S3Client<P, D>,
+    bucket: &str,
+    key: &str,
+    part_requests: &mut [UploadPartRequest],
+) -> Result<(), String>
+where
+    P: ProvideAwsCredentials,
+    D: DispatchSignedRequest,
+{
+    let created = client
+        .create_multipart_upload(&CreateMultipartUploadRequest {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            ..Default::default()
+        })
+        .map_err(|e| format!("{:?}", e))?;
+    let upload_id = created
+        .upload_id
+        .ok_or_else(|| "S3 didn't give an upload ID".to_string())?;
+
+    let mut parts: Vec<CompletedPart> = Vec::new();
+    for part_request in part_requests.iter_mut() {
+        part_request.upload_id = upload_id.clone();
+        match client.upload_part(part_request) {
+            Ok(output) => parts.push(CompletedPart {
+                e_tag: output.e_tag,
+                part_number: Some(part_request.part_number),
+            }),
+            Err(e) => {
+                let _ = client.abort_multipart_upload(&AbortMultipartUploadRequest {
+                    bucket: bucket.to_owned(),
+                    key: key.to_owned(),
+                    upload_id: upload_id.clone(),
+                    ..Default::default()
+                });
+                return Err(format!("part {}: {:?}", part_request.part_number, e));
+            }
+        }
+    }
+
+    client
+        .complete_multipart_upload(&CompleteMultipartUploadRequest {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            upload_id: upload_id,
+            multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+            ..Default::default()
+        })
+        .map(|_| ())
+        .map_err(|e| format!("{:?}", e))
+}
+
+fn generate_mode_string() -> String {
+    if MODE.committer_count {
+        return "committers".to_string();
+    }
+    if MODE.commit_count {
+        return "commits".to_string();
+    }
+    if MODE.stars {
+        return "stars".to_string();
+    }
+    if MODE.forks {
+        return "forks".to_string();
+    }
+    if MODE.pull_requests {
+        return "pullrequests".to_string();
+    }
+    if MODE.issues {
+        return "issues".to_string();
+    }
+    if MODE.first_contributions {
+        return "firstcontributions".to_string();
+    }
+    if MODE.orgs {
+        return "orgs".to_string();
+    }
+    if MODE.releases {
+        return "releases".to_string();
+    }
+    if MODE.reviewers {
+        return "reviewers".to_string();
+    }
+    if MODE.histogram {
+        return "histogram".to_string();
+    }
+    if MODE.subset {
+        return "subset".to_string();
+    }
+    if MODE.repartition {
+        return "repartition".to_string();
+    }
+    "repomapping".to_string()
+}
+
+// check things like dryrun etc
+fn environment_check() {
+    if OUTPUT_DIR.is_none() {
+        let _ = env::var("DESTBUCKET").expect("Need DESTBUCKET or OUTPUT_DIR set");
+    }
+    let _ = env::var("GHABUCKET").expect("Need GHABUCKET set to bucket name");
+    let _ = env::var("GHAYEAR").expect("Need GHAYEAR set to year to process");
+    let _ = env::var("GHAHOURS")
+        .expect("Need GHAHOURS set to number of hours (files) to process")
+        .parse::<i64>()
+        .expect("Please set GHAHOURS to an integer value");
+    if !MODE_NAMES.contains(&MODE_NAME.as_str()) {
+        panic!("GHAMODE should be one of {:?}, got {}", MODE_NAMES, *MODE_NAME);
+    }
+    // Read here so a bad filter stops the run before it starts, not in a thread that's fetching events.
+    lazy_static::initialize(&EVENT_FILTER);
+    if MODE.subset {
+        lazy_static::initialize(&REPO_SELECTOR);
+    }
+    if MODE.repartition {
+        lazy_static::initialize(&PARTITIONING);
+    }
+}
+
+/// Make the list of GHA input files.
+fn make_list() -> Vec<String> {
+    let mut file_list = construct_list_of_ingest_files();
+    let mut rng = thread_rng();
+    // Shuffling the list prevents hotspot reads from S3, boosting download performance.
+    rng.shuffle(&mut file_list);
+    println!("file list is now {:#?}", file_list);
+    file_list
+}
+
+/// Get the events the current mode needs from the files specified on S3.
+/// Each file is an hour of events, so that's where we check for accounts busy enough to be bots.
+/// Modes that pass events on untouched get the line each event was parsed from too.
+fn get_event_subset_for_mode<
+    P: ProvideAwsCredentials + Sync + Send,
+    D: DispatchSignedRequest + Sync + Send,
+>(
+    chunk: &[String],
+    client: &S3Client<P, D>,
+) -> Vec<EventWorkItem> {
+    chunk
+        .par_iter()
+        // todo: don't panic here (issue only when S3 kicks back errors)
+        .flat_map(|file_name| {
+            if MODE.subset || MODE.repartition {
+                return download_and_parse_raw_file(file_name, &client)
+                    .expect("Issue with file ingest")
+                    .into_iter()
+                    .filter(|raw| is_event_for_mode(&raw.event))
+                    .map(|raw| EventWorkItem {
+                        event: raw.event,
+                        raw_line: Some(raw.line),
+                        high_rate_actor: false,
+                        no_more_work: false,
+                    })
+                    .collect::<Vec<EventWorkItem>>();
+            }
+            let events = download_and_parse_any_file(file_name, &client).expect("Issue with file ingest");
+            let busy_actors = match MODE.bots {
+                BotHandling::Keep => HashSet::new(),
+                _ => BOT_CLASSIFIER.high_rate_actors(&events),
+            };
+            events
+                .into_iter()
+                .filter(|event| is_event_for_mode(event))
+                .map(|event| EventWorkItem {
+                    high_rate_actor: match event.actor.login {
+                        Some(ref login) => busy_actors.contains(login),
+                        None => false,
+                    },
+                    event: event,
+                    raw_line: None,
+                    no_more_work: false,
+                })
+                .collect::<Vec<EventWorkItem>>()
+        })
+        .collect()
+}
+
+/// Committer count and first contributions want commit/PR events, commit count wants pushes, stars wants watches,
+/// forks wants forks, pull requests wants PR events, issues wants issue and issue comment events, releases wants
+/// tag, branch and release events, reviewers wants review and review comment events, subset wants the repos it was
+/// asked for and repo mapping, org rollups, the histogram and repartitioning take everything.  With ORG_FILTER only
+/// events for those orgs are wanted, and with EVENT_FILTER only events it matches.
+fn is_event_for_mode(event: &Event) -> bool {
+    if !MODE.org_filter.is_empty() && !MODE.org_filter.contains(&event.org_login().to_lowercase()) {
+        return false;
+    }
+    if let Some(ref filter) = *EVENT_FILTER {
+        if !filter.matches(event) {
+            return false;
+        }
+    }
+    if MODE.committer_count || MODE.first_contributions {
+        event.is_commit_event()
+    } else if MODE.commit_count {
+        event.is_direct_push_event()
+    } else if MODE.stars {
+        event.is_star_event()
+    } else if MODE.forks {
+        event.is_fork_event()
+    } else if MODE.pull_requests {
+        event.event_type == "PullRequestEvent"
+    } else if MODE.issues {
+        event.is_issue_event()
+    } else if MODE.releases {
+        event.is_release_event()
+    } else if MODE.reviewers {
+        event.is_review_event()
+    } else if MODE.subset {
+        REPO_SELECTOR.is_selected(event.repo.id, &event.repo.name)
+    } else {
+        true
+    }
+}
+
+/// Struct representing a completed item of work to upload to S3.
+/// Also used as a "no more items" signal.
+#[derive(Debug, Clone)]
+struct WorkItem {
+    sql: String,
+    s3_bucket_name: String,
+    s3_file_location: String,
+    no_more_work: bool,
+}
+
+/// Struct for what mode we're in.
+#[derive(Debug, Clone)]
+struct Mode {
+    committer_count: bool,
+    repo_mapping: bool,
+    commit_count: bool,
+    stars: bool,
+    forks: bool,
+    fork_stats: bool,
+    pull_requests: bool,
+    pr_stats: bool,
+    issues: bool,
+    issue_stats: bool,
+    first_contributions: bool,
+    orgs: bool,
+    org_filter: HashSet<String>,
+    releases: bool,
+    release_stats: bool,
+    reviewers: bool,
+    histogram: bool,
+    subset: bool,
+    repartition: bool,
+    bus_factor: bool,
+    verify: bool,
+    check_input: bool,
+    attribute_to_authors: bool,
+    committer_bucket: Option<TimeBucket>,
+    bots: BotHandling,
+    output_format: OutputFormat,
+    output_codec: Codec,
+    dry_run: bool,
+}
+
+/// Struct representing a file to download and parse.
+/// Also allows a "no more work" signal to be passed.
+#[derive(Debug, Clone)]
+struct FileWorkItem {
+    file: String,
+    no_more_work: bool,
+}
+
+/// Struct representing a 2015 and later event.
+/// high_rate_actor is set if the event's actor made more events in that hour than a person would.
+/// raw_line is the event's JSON, for modes that want it.
+/// Also allows a "no more work" signal to be passed.
+#[derive(Debug, Clone)]
+struct EventWorkItem {
+    event: Event,
+    raw_line: Option<String>,
+    high_rate_actor: bool,
+    no_more_work: bool,
+}
+
+lazy_static! {
+    static ref YEAR: i32 = {
+        env::var("GHAYEAR").expect("Please set GHAYEAR env var").parse::<i32>().expect("Please set GHAYEAR env var to an integer value.")
+    };
+}
+
+// if a repo ID shows up twice the collection we received has a duplicate in it
+fn dupes_in(repo_id_mappings: &[RepoIdToName]) -> bool {
+    let mut repo_ids = repo_id_mappings
+        .iter()
+        .map(|item| item.repo_id)
+        .collect::<Vec<i64>>();
+    let old_count = repo_ids.len();
+    repo_ids.sort();
+    repo_ids.dedup();
+    if old_count != repo_ids.len() {
+        return true;
+    }
+    false
+}
+
+/// SHA1 of the value, used so we don't publish logins or emails.
+fn obfuscate_id(value: &str) -> String {
+    let mut sha_er = sha1::Sha1::new();
+    sha_er.update(value.as_bytes());
+    sha_er.digest().to_string()
+}
+
+/// Escape a free form value, such as a commit author's name, for use in a SQL string literal.
+fn sql_escape(value: &str) -> String {
+    value.replace("'", "''")
+}
+
+// Since we're doing nothing on conflict, we don't need to separate out any duplicates we may have received.
+// with_period adds the start of the committer's time bucket and flag_bots adds if the committer is a bot.
+fn group_committer_sql_insert_par(
+    committers: &[CommitEvent],
+    obfuscate: bool,
+    flag_bots: bool,
+    with_period: bool,
+) -> String {
+    group_repo_actor_sql_insert_par("committer_repo_id_names", committers, obfuscate, flag_bots, with_period)
+}
+
+/// Who did something in which repo, for committers and reviewers.
+fn group_repo_actor_sql_insert_par(
+    table: &str,
+    committers: &[CommitEvent],
+    obfuscate: bool,
+    flag_bots: bool,
+    with_period: bool,
+) -> String {
+    let mut columns = vec!["repo_id", "actor_name"];
+    if with_period {
+        columns.push("period");
+    }
+    // A committer flagged as a bot in any batch stays flagged.
+    let key = columns.join(", ");
+    if flag_bots {
+        columns.push("is_bot");
+    }
+    let columns = columns.join(", ");
+    committers
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|chunk| {
+                    let actor_name = match obfuscate {
+                        true => obfuscate_id(&chunk.actor),
+                        false => chunk.actor.clone(),
+                    };
+                    let mut values = vec![chunk.repo_id.to_string(), format!("'{}'", actor_name)];
+                    if with_period {
+                        values.push(match chunk.period {
+                            Some(period) => format!("'{}'", period),
+                            None => "NULL".to_string(),
+                        });
+                    }
+                    if flag_bots {
+                        values.push(chunk.is_bot.to_string());
+                    }
+                    format!("({})", values.join(", "))
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            if flag_bots {
+                format!(
+                    "INSERT INTO {table} ({}) VALUES {}
+ON CONFLICT ({}) DO UPDATE SET is_bot = {table}.is_bot OR excluded.is_bot;",
+                    columns,
+                    row_to_insert,
+                    key,
+                    table = table
+                )
+            } else {
+                format!("INSERT INTO {} ({}) VALUES {} ON CONFLICT DO NOTHING;", table, columns, row_to_insert)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// The same SHA can be in many repos (forks), so the conflict is on the pair.
+fn group_commit_sql_insert_par(commits: &[AuthoredCommit], obfuscate: bool) -> String {
+    commits
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|commit| {
+                    let author = match obfuscate {
+                        true => obfuscate_id(&commit.author),
+                        false => sql_escape(&commit.author),
+                    };
+                    format!("({}, '{}', '{}')", commit.repo_id, commit.sha, author)
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_commits (repo_id, sha, author) VALUES {} ON CONFLICT (repo_id, sha) DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_commit_count_sql_insert_par(counts: &[CommitCount], obfuscate: bool) -> String {
+    counts
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|count| {
+                    let author = match obfuscate {
+                        true => obfuscate_id(&count.author),
+                        false => sql_escape(&count.author),
+                    };
+                    format!("({}, '{}', '{}', {})", count.repo_id, author, count.period, count.commits)
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_commit_counts (repo_id, author, period, commits) VALUES {}
+ON CONFLICT (repo_id, author, period) DO UPDATE SET commits = excluded.commits;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_daily_stars_sql_insert_par(daily_stars: &[DailyStars]) -> String {
+    daily_stars
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|day| format!("({}, '{}', {})", day.repo_id, day.starred_on, day.stars))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_daily_stars (repo_id, starred_on, stars) VALUES {}
+ON CONFLICT (repo_id, starred_on) DO UPDATE SET stars = excluded.stars;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_star_stats_sql_insert(stats: &[StarStats]) -> String {
+    stats
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|stat| format!("({}, {})", stat.repo_id, stat.stargazers))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_star_stats (repo_id, stargazers) VALUES {}
+ON CONFLICT (repo_id) DO UPDATE SET stargazers = excluded.stargazers;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Fork owners are part of the fork's public name so they aren't obfuscated.
+fn group_forks_sql_insert_par(forks: &[ForkEvent]) -> String {
+    forks
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|fork| {
+                    format!(
+                        "({}, {}, '{}', '{}')",
+                        fork.parent_repo_id, fork.fork_repo_id, fork.fork_owner, fork.created_at
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_forks (parent_repo_id, fork_repo_id, fork_owner, created_at) VALUES {} ON CONFLICT DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Stats are for the whole run, so a later run replaces them.  The merge, issue and release stats are the same.
+fn group_fork_stats_sql_insert(stats: &[ForkStats]) -> String {
+    stats
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|stat| format!("({}, {}, {})", stat.repo_id, stat.fork_count, stat.depth))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_fork_stats (repo_id, fork_count, depth) VALUES {}
+ON CONFLICT (repo_id) DO UPDATE SET (fork_count, depth) = (excluded.fork_count, excluded.depth);", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Quoted timestamp, or NULL if we don't have one.
+fn sql_timestamp(timestamp: &Option<DateTime<Utc>>) -> String {
+    match *timestamp {
+        Some(ref timestamp) => format!("'{}'", timestamp),
+        None => "NULL".to_string(),
+    }
+}
+
+/// Number, or NULL if we don't have one.
+fn sql_number(number: &Option<i64>) -> String {
+    match *number {
+        Some(number) => number.to_string(),
+        None => "NULL".to_string(),
+    }
+}
+
+// Newer events replace what we know about a PR, like the repo mapping does for names.
+// Each chunk has a PR at most once: a statement can't update the same row twice.
+fn group_pr_sql_insert_par(prs: &[PrLifecycle], obfuscate: bool) -> String {
+    prs.par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|pr| {
+                    let author = match obfuscate {
+                        true => obfuscate_id(&pr.author),
+                        false => pr.author.clone(),
+                    };
+                    format!(
+                        "({}, {}, '{}', {}, {}, {}, {}, {}, '{}', {}, '{}')",
+                        pr.repo_id,
+                        pr.number,
+                        author,
+                        sql_timestamp(&pr.opened_at),
+                        sql_timestamp(&pr.closed_at),
+                        sql_timestamp(&pr.merged_at),
+                        sql_number(&pr.additions),
+                        sql_number(&pr.deletions),
+                        sql_escape(&pr.base_branch),
+                        pr.cross_repo,
+                        pr.updated_at
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO pull_requests (repo_id, number, author, opened_at, closed_at, merged_at, additions, deletions, base_branch, cross_repo, updated_at)
+VALUES {}
+ON CONFLICT (repo_id, number) DO UPDATE SET (author, opened_at, closed_at, merged_at, additions, deletions, base_branch, cross_repo, updated_at) = (excluded.author, excluded.opened_at, excluded.closed_at, excluded.merged_at, excluded.additions, excluded.deletions, excluded.base_branch, excluded.cross_repo, excluded.updated_at)
+WHERE pull_requests.updated_at < EXCLUDED.updated_at;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_merge_stats_sql_insert(stats: &[MergeStats]) -> String {
+    stats
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|stat| {
+                    format!(
+                        "({}, {}, {}, {}, {})",
+                        stat.repo_id, stat.merged_prs, stat.mean_seconds, stat.median_seconds, stat.p90_seconds
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_merge_stats (repo_id, merged_prs, mean_seconds, median_seconds, p90_seconds) VALUES {}
+ON CONFLICT (repo_id) DO UPDATE SET (merged_prs, mean_seconds, median_seconds, p90_seconds) = (excluded.merged_prs, excluded.mean_seconds, excluded.median_seconds, excluded.p90_seconds);", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Event IDs are unique in the archive so they make a good conflict key, here and for releases and reviews.
+fn group_issue_sql_insert_par(activities: &[IssueActivity], obfuscate: bool) -> String {
+    activities
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|activity| {
+                    let (actor_name, issue_author) = match obfuscate {
+                        true => (obfuscate_id(&activity.actor), obfuscate_id(&activity.issue_author)),
+                        false => (activity.actor.clone(), activity.issue_author.clone()),
+                    };
+                    format!(
+                        "({}, {}, {}, '{}', '{}', '{}', '{}', '{}', {}, {})",
+                        activity.event_id,
+                        activity.repo_id,
+                        activity.number,
+                        sql_escape(&activity.action),
+                        actor_name,
+                        sql_escape(&activity.label),
+                        activity.happened_at,
+                        issue_author,
+                        sql_timestamp(&activity.opened_at),
+                        activity.is_pull_request
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO issue_events (event_id, repo_id, number, action, actor_name, label, happened_at, issue_author, opened_at, is_pull_request) VALUES {} ON CONFLICT (event_id) DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_issue_stats_sql_insert(stats: &[IssueStats]) -> String {
+    stats
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|stat| {
+                    format!(
+                        "({}, {}, {}, {}, {}, {})",
+                        stat.repo_id,
+                        stat.issues_opened,
+                        stat.issues_closed,
+                        stat.comments,
+                        stat.responded_issues,
+                        sql_number(&stat.median_first_response_seconds)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_issue_stats (repo_id, issues_opened, issues_closed, comments, responded_issues, median_first_response_seconds) VALUES {}
+ON CONFLICT (repo_id) DO UPDATE SET (issues_opened, issues_closed, comments, responded_issues, median_first_response_seconds) = (excluded.issues_opened, excluded.issues_closed, excluded.comments, excluded.responded_issues, excluded.median_first_response_seconds);", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// The opposite of the repo mapping: the earliest time wins, so loading batches and runs in any order
+// ends up with the first contribution.  With flag_bots the bot flag is ORed whichever time wins.
+fn group_first_contribution_sql_insert_par(
+    contributions: &[FirstContribution],
+    obfuscate: bool,
+    flag_bots: bool,
+) -> String {
+    contributions
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|contribution| {
+                    let actor_name = match obfuscate {
+                        true => obfuscate_id(&contribution.actor),
+                        false => contribution.actor.clone(),
+                    };
+                    match flag_bots {
+                        true => format!(
+                            "({}, '{}', '{}', {})",
+                            contribution.repo_id, actor_name, contribution.first_contributed_at, contribution.is_bot
+                        ),
+                        false => format!(
+                            "({}, '{}', '{}')",
+                            contribution.repo_id, actor_name, contribution.first_contributed_at
+                        ),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            if flag_bots {
+                return format!("INSERT INTO first_contributions (repo_id, actor_name, first_contributed_at, is_bot)
+VALUES {}
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET first_contributed_at = LEAST(first_contributions.first_contributed_at, excluded.first_contributed_at),
+is_bot = first_contributions.is_bot OR excluded.is_bot;", row_to_insert);
+            }
+            format!("INSERT INTO first_contributions (repo_id, actor_name, first_contributed_at)
+VALUES {}
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET first_contributed_at = excluded.first_contributed_at
+WHERE first_contributions.first_contributed_at > EXCLUDED.first_contributed_at;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_release_sql_insert_par(activities: &[ReleaseActivity], obfuscate: bool) -> String {
+    activities
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|activity| {
+                    let actor_name = match obfuscate {
+                        true => obfuscate_id(&activity.actor),
+                        false => activity.actor.clone(),
+                    };
+                    format!(
+                        "({}, {}, '{}', '{}', '{}', {}, '{}', '{}')",
+                        activity.event_id,
+                        activity.repo_id,
+                        activity.kind,
+                        sql_escape(&activity.action),
+                        sql_escape(&activity.name),
+                        activity.prerelease,
+                        actor_name,
+                        activity.happened_at
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_releases (event_id, repo_id, kind, action, name, prerelease, actor_name, happened_at) VALUES {} ON CONFLICT (event_id) DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_release_cadence_sql_insert(stats: &[ReleaseCadence]) -> String {
+    stats
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|stat| {
+                    format!(
+                        "({}, {}, {}, '{}', '{}', {}, {})",
+                        stat.repo_id,
+                        stat.releases,
+                        stat.prereleases,
+                        stat.first_release_at,
+                        stat.last_release_at,
+                        sql_number(&stat.mean_seconds_between),
+                        sql_number(&stat.median_seconds_between)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO repo_release_stats (repo_id, releases, prereleases, first_release_at, last_release_at, mean_seconds_between, median_seconds_between) VALUES {}
+ON CONFLICT (repo_id) DO UPDATE SET (releases, prereleases, first_release_at, last_release_at, mean_seconds_between, median_seconds_between) = (excluded.releases, excluded.prereleases, excluded.first_release_at, excluded.last_release_at, excluded.mean_seconds_between, excluded.median_seconds_between);", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_review_sql_insert_par(reviews: &[PrReview], obfuscate: bool) -> String {
+    reviews
+        .par_chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|review| {
+                    let reviewer = match obfuscate {
+                        true => obfuscate_id(&review.reviewer),
+                        false => review.reviewer.clone(),
+                    };
+                    format!(
+                        "({}, {}, {}, '{}', '{}', '{}')",
+                        review.event_id,
+                        review.repo_id,
+                        review.number,
+                        reviewer,
+                        sql_escape(&review.state),
+                        review.submitted_at
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO pr_reviews (event_id, repo_id, number, reviewer, state, submitted_at) VALUES {} ON CONFLICT (event_id) DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Counts are for the whole run, so they replace what's there.
+fn group_org_event_counts_sql_insert(counts: &[OrgEventCount]) -> String {
+    counts
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|count| format!("('{}', '{}', {})", count.org, count.event_type, count.events))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO org_event_counts (org, event_type, events) VALUES {}
+ON CONFLICT (org, event_type) DO UPDATE SET events = excluded.events;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_org_repos_sql_insert(repos: &[(&str, i64)]) -> String {
+    repos
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|&(org, repo_id)| format!("('{}', {})", org, repo_id))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            format!("INSERT INTO org_repos (org, repo_id) VALUES {} ON CONFLICT DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn group_org_committers_sql_insert(committers: &[(&str, &str, bool)], obfuscate: bool, flag_bots: bool) -> String {
+    committers
+        .chunks(20)
+        .map(|chunk| {
+            let row_to_insert: String = chunk
+                .iter()
+                .map(|&(org, actor, is_bot)| {
+                    let actor_name = match obfuscate {
+                        true => obfuscate_id(actor),
+                        false => sql_escape(actor),
+                    };
+                    match flag_bots {
+                        true => format!("('{}', '{}', {})", org, actor_name, is_bot),
+                        false => format!("('{}', '{}')", org, actor_name),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            if flag_bots {
+                return format!("INSERT INTO org_committers (org, actor_name, is_bot) VALUES {}
+ON CONFLICT (org, actor_name) DO UPDATE SET is_bot = org_committers.is_bot OR excluded.is_bot;", row_to_insert);
+            }
+            format!("INSERT INTO org_committers (org, actor_name) VALUES {} ON CONFLICT DO NOTHING;", row_to_insert)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
 
 // It's possible repo_id is in here twice, which causes an error from Postgres.
 fn group_repo_id_sql_insert(repo_id_mappings: &[RepoIdToName]) -> String {
@@ -612,27 +2691,39 @@ mod tests {
         items.push(CommitEvent {
             actor: "foo".to_string(),
             repo_id: 1,
+            period: None,
+            is_bot: false,
         });
         items.push(CommitEvent {
             actor: "bar".to_string(),
             repo_id: 1,
+            period: None,
+            is_bot: false,
         });
         // this dupe should go away after sorting:
         items.push(CommitEvent {
             actor: "bar".to_string(),
             repo_id: 1,
+            period: None,
+            is_bot: false,
         });
         items.push(CommitEvent {
             actor: "foo".to_string(),
             repo_id: 2,
+            period: None,
+            is_bot: false,
         });
         items.push(CommitEvent {
             actor: "bar".to_string(),
             repo_id: 2,
+            period: None,
+            is_bot: false,
         });
         items.push(CommitEvent {
             actor: "baz".to_string(),
             repo_id: 2,
+            period: None,
+            is_bot: false,
         });
 
         // ensure sorting removes dupes
@@ -644,16 +2735,458 @@ mod tests {
         // group sql statement works
         let expected_sql = "INSERT INTO committer_repo_id_names (repo_id, actor_name) VALUES (1, 'bar'), (2, 'bar'), (2, 'baz'), (1, 'foo'), (2, 'foo') ON CONFLICT DO NOTHING;";
 
-        assert_eq!(expected_sql, group_committer_sql_insert_par(&items, false));
+        assert_eq!(expected_sql, group_committer_sql_insert_par(&items, false, false, false));
 
         let expected_sql_obf = "INSERT INTO committer_repo_id_names (repo_id, actor_name) VALUES (1, '62cdb7020ff920e5aa642c3d4066950dd1f01f4d'), (2, '62cdb7020ff920e5aa642c3d4066950dd1f01f4d'), (2, 'bbe960a25ea311d21d40669e93df2003ba9b90a2'), (1, '0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33'), (2, '0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33') ON CONFLICT DO NOTHING;";
 
         assert_eq!(
             expected_sql_obf,
-            group_committer_sql_insert_par(&items, true)
+            group_committer_sql_insert_par(&items, true, false, false)
+        );
+    }
+
+    #[test]
+    fn actor_columns_are_obfuscated() {
+        use rusty_von_humboldt::records::{Record, Value};
+        use rusty_von_humboldt::types::IssueActivity;
+        use obfuscate_actors;
+
+        let activity = IssueActivity {
+            event_id: 1,
+            repo_id: 2,
+            number: 3,
+            action: "opened".to_string(),
+            actor: "foo".to_string(),
+            label: "".to_string(),
+            happened_at: "2017-05-01T07:00:00Z".parse().unwrap(),
+            issue_author: "bar".to_string(),
+            opened_at: None,
+            is_pull_request: false,
+        };
+        let values = obfuscate_actors(&IssueActivity::columns(), activity.values());
+        assert_eq!(Value::Utf8("opened".to_string()), values[3]);
+        assert_eq!(Value::Utf8("0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33".to_string()), values[4]);
+        assert_eq!(Value::Utf8("62cdb7020ff920e5aa642c3d4066950dd1f01f4d".to_string()), values[7]);
+    }
+
+    #[test]
+    fn committer_bot_flags() {
+        use rusty_von_humboldt::types::CommitEvent;
+        use {dedup_committers, group_committer_sql_insert_par};
+
+        let mut items: Vec<CommitEvent> = Vec::new();
+        items.push(CommitEvent {
+            actor: "renovate-bot".to_string(),
+            repo_id: 1,
+            period: None,
+            is_bot: false,
+        });
+        // same account flagged as a bot in another hour:
+        items.push(CommitEvent {
+            actor: "renovate-bot".to_string(),
+            repo_id: 1,
+            period: None,
+            is_bot: true,
+        });
+        items.push(CommitEvent {
+            actor: "foo".to_string(),
+            repo_id: 1,
+            period: None,
+            is_bot: false,
+        });
+
+        dedup_committers(&mut items);
+        assert_eq!(2, items.len());
+
+        let expected_sql = "INSERT INTO committer_repo_id_names (repo_id, actor_name, is_bot) VALUES (1, 'foo', false), (1, 'renovate-bot', true)
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET is_bot = committer_repo_id_names.is_bot OR excluded.is_bot;";
+        assert_eq!(expected_sql, group_committer_sql_insert_par(&items, false, true, false));
+    }
+
+    #[test]
+    fn committers_per_period() {
+        use rusty_von_humboldt::types::CommitEvent;
+        use chrono::NaiveDate;
+        use {dedup_committers, group_committer_sql_insert_par};
+
+        let mut items: Vec<CommitEvent> = Vec::new();
+        items.push(CommitEvent {
+            actor: "foo".to_string(),
+            repo_id: 1,
+            period: Some(NaiveDate::from_ymd(2016, 2, 1)),
+            is_bot: false,
+        });
+        items.push(CommitEvent {
+            actor: "foo".to_string(),
+            repo_id: 1,
+            period: Some(NaiveDate::from_ymd(2016, 1, 1)),
+            is_bot: false,
+        });
+        // same month as the first one:
+        items.push(CommitEvent {
+            actor: "foo".to_string(),
+            repo_id: 1,
+            period: Some(NaiveDate::from_ymd(2016, 2, 1)),
+            is_bot: false,
+        });
+
+        dedup_committers(&mut items);
+        assert_eq!(2, items.len());
+
+        let expected_sql = "INSERT INTO committer_repo_id_names (repo_id, actor_name, period) VALUES (1, 'foo', '2016-01-01'), (1, 'foo', '2016-02-01') ON CONFLICT DO NOTHING;";
+        assert_eq!(expected_sql, group_committer_sql_insert_par(&items, false, false, true));
+    }
+
+    #[test]
+    fn multi_row_insert_commits() {
+        use rusty_von_humboldt::types::AuthoredCommit;
+        use group_commit_sql_insert_par;
+
+        let mut items: Vec<AuthoredCommit> = Vec::new();
+        items.push(AuthoredCommit {
+            repo_id: 1,
+            sha: "abc".to_string(),
+            author: "foo@example.com".to_string(),
+        });
+        // same commit pushed again should be deduped:
+        items.push(AuthoredCommit {
+            repo_id: 1,
+            sha: "abc".to_string(),
+            author: "foo@example.com".to_string(),
+        });
+        items.push(AuthoredCommit {
+            repo_id: 2,
+            sha: "def".to_string(),
+            author: "Pat O'Brien".to_string(),
+        });
+
+        items.sort();
+        items.dedup_by(|a, b| a.repo_id == b.repo_id && a.sha == b.sha);
+        assert_eq!(2, items.len());
+
+        let expected_sql = "INSERT INTO repo_commits (repo_id, sha, author) VALUES (1, 'abc', 'foo@example.com'), (2, 'def', 'Pat O''Brien') ON CONFLICT (repo_id, sha) DO NOTHING;";
+
+        assert_eq!(expected_sql, group_commit_sql_insert_par(&items, false));
+    }
+
+    #[test]
+    fn commit_counts_replace() {
+        use rusty_von_humboldt::types::CommitCount;
+        use chrono::NaiveDate;
+        use group_commit_count_sql_insert_par;
+
+        let counts = vec![
+            CommitCount {
+                repo_id: 1,
+                author: "".to_string(),
+                period: NaiveDate::from_ymd(2016, 1, 1),
+                commits: 22,
+            },
+            CommitCount {
+                repo_id: 1,
+                author: "Pat O'Brien".to_string(),
+                period: NaiveDate::from_ymd(2016, 1, 1),
+                commits: 3,
+            },
+        ];
+
+        let expected_sql = "INSERT INTO repo_commit_counts (repo_id, author, period, commits) VALUES (1, '', '2016-01-01', 22), (1, 'Pat O''Brien', '2016-01-01', 3)
+ON CONFLICT (repo_id, author, period) DO UPDATE SET commits = excluded.commits;";
+
+        assert_eq!(expected_sql, group_commit_count_sql_insert_par(&counts, false));
+    }
+
+    #[test]
+    fn sql_written_in_slices() {
+        use rusty_von_humboldt::types::{AuthoredCommit, RepoIdToName};
+        use {group_commit_sql_insert_par, group_repo_id_sql_insert, write_sql_slices, SQL_SLICE_RECORDS};
+
+        let commits: Vec<AuthoredCommit> = (0..SQL_SLICE_RECORDS * 2 + 7)
+            .map(|index| AuthoredCommit {
+                repo_id: index as i64,
+                sha: format!("{:x}", index),
+                author: "foo@example.com".to_string(),
+            })
+            .collect();
+        let mut sql = Vec::new();
+        write_sql_slices(&mut sql, &commits, |slice| group_commit_sql_insert_par(slice, false));
+        assert_eq!(group_commit_sql_insert_par(&commits, false).into_bytes(), sql);
+
+        let mut sql = Vec::new();
+        write_sql_slices(&mut sql, &[] as &[RepoIdToName], group_repo_id_sql_insert);
+        assert!(sql.is_empty());
+    }
+
+    #[test]
+    fn multi_row_insert_stars() {
+        use rusty_von_humboldt::stars::{DailyStars, StarStats};
+        use chrono::NaiveDate;
+        use {group_daily_stars_sql_insert_par, group_star_stats_sql_insert};
+
+        let daily_stars = vec![
+            DailyStars {
+                repo_id: 1,
+                starred_on: NaiveDate::from_ymd(2016, 1, 1),
+                stars: 2,
+            },
+            DailyStars {
+                repo_id: 1,
+                starred_on: NaiveDate::from_ymd(2016, 1, 2),
+                stars: 1,
+            },
+        ];
+        let expected_sql = "INSERT INTO repo_daily_stars (repo_id, starred_on, stars) VALUES (1, '2016-01-01', 2), (1, '2016-01-02', 1)
+ON CONFLICT (repo_id, starred_on) DO UPDATE SET stars = excluded.stars;";
+        assert_eq!(expected_sql, group_daily_stars_sql_insert_par(&daily_stars));
+
+        let stats = vec![StarStats {
+            repo_id: 1,
+            stargazers: 2,
+        }];
+        let expected_sql = "INSERT INTO repo_star_stats (repo_id, stargazers) VALUES (1, 2)
+ON CONFLICT (repo_id) DO UPDATE SET stargazers = excluded.stargazers;";
+        assert_eq!(expected_sql, group_star_stats_sql_insert(&stats));
+    }
+
+    #[test]
+    fn multi_row_insert_forks() {
+        use rusty_von_humboldt::types::ForkEvent;
+        use rusty_von_humboldt::forks::ForkStats;
+        use chrono::{TimeZone, Utc};
+        use {group_fork_stats_sql_insert, group_forks_sql_insert_par};
+
+        let forks = vec![
+            ForkEvent {
+                parent_repo_id: 1,
+                fork_repo_id: 2,
+                fork_owner: "foo".to_string(),
+                created_at: Utc.ymd(2016, 1, 1).and_hms(1, 2, 3),
+            },
+        ];
+        let expected_sql = "INSERT INTO repo_forks (parent_repo_id, fork_repo_id, fork_owner, created_at) VALUES (1, 2, 'foo', '2016-01-01 01:02:03 UTC') ON CONFLICT DO NOTHING;";
+        assert_eq!(expected_sql, group_forks_sql_insert_par(&forks));
+
+        let stats = vec![
+            ForkStats {
+                repo_id: 1,
+                fork_count: 1,
+                depth: 0,
+            },
+            ForkStats {
+                repo_id: 2,
+                fork_count: 0,
+                depth: 1,
+            },
+        ];
+        let expected_stats_sql = "INSERT INTO repo_fork_stats (repo_id, fork_count, depth) VALUES (1, 1, 0), (2, 0, 1)
+ON CONFLICT (repo_id) DO UPDATE SET (fork_count, depth) = (excluded.fork_count, excluded.depth);";
+        assert_eq!(expected_stats_sql, group_fork_stats_sql_insert(&stats));
+    }
+
+    #[test]
+    fn multi_row_insert_pull_requests() {
+        use rusty_von_humboldt::types::PrLifecycle;
+        use chrono::{TimeZone, Utc};
+        use group_pr_sql_insert_par;
+
+        let prs = vec![
+            PrLifecycle {
+                repo_id: 1,
+                number: 7,
+                author: "foo".to_string(),
+                opened_at: Some(Utc.ymd(2016, 1, 1).and_hms(0, 0, 0)),
+                closed_at: None,
+                merged_at: None,
+                additions: Some(3),
+                deletions: None,
+                base_branch: "master".to_string(),
+                cross_repo: true,
+                updated_at: Utc.ymd(2016, 1, 1).and_hms(0, 0, 0),
+            },
+        ];
+        let expected_sql = "INSERT INTO pull_requests (repo_id, number, author, opened_at, closed_at, merged_at, additions, deletions, base_branch, cross_repo, updated_at)
+VALUES (1, 7, 'foo', '2016-01-01 00:00:00 UTC', NULL, NULL, 3, NULL, 'master', true, '2016-01-01 00:00:00 UTC')
+ON CONFLICT (repo_id, number) DO UPDATE SET (author, opened_at, closed_at, merged_at, additions, deletions, base_branch, cross_repo, updated_at) = (excluded.author, excluded.opened_at, excluded.closed_at, excluded.merged_at, excluded.additions, excluded.deletions, excluded.base_branch, excluded.cross_repo, excluded.updated_at)
+WHERE pull_requests.updated_at < EXCLUDED.updated_at;";
+        assert_eq!(expected_sql, group_pr_sql_insert_par(&prs, false));
+    }
+
+    #[test]
+    fn multi_row_insert_issues() {
+        use rusty_von_humboldt::types::IssueActivity;
+        use chrono::{TimeZone, Utc};
+        use group_issue_sql_insert_par;
+
+        let activities = vec![
+            IssueActivity {
+                event_id: 99,
+                repo_id: 1,
+                number: 7,
+                action: "labeled".to_string(),
+                actor: "foo".to_string(),
+                label: "won't fix".to_string(),
+                happened_at: Utc.ymd(2016, 1, 1).and_hms(1, 0, 0),
+                issue_author: "bar".to_string(),
+                opened_at: None,
+                is_pull_request: false,
+            },
+        ];
+        let expected_sql = "INSERT INTO issue_events (event_id, repo_id, number, action, actor_name, label, happened_at, issue_author, opened_at, is_pull_request) VALUES (99, 1, 7, 'labeled', 'foo', 'won''t fix', '2016-01-01 01:00:00 UTC', 'bar', NULL, false) ON CONFLICT (event_id) DO NOTHING;";
+        assert_eq!(expected_sql, group_issue_sql_insert_par(&activities, false));
+    }
+
+    #[test]
+    fn multi_row_insert_first_contributions() {
+        use rusty_von_humboldt::types::FirstContribution;
+        use chrono::{TimeZone, Utc};
+        use group_first_contribution_sql_insert_par;
+
+        let mut items = vec![
+            FirstContribution {
+                repo_id: 1,
+                actor: "foo".to_string(),
+                first_contributed_at: Utc.ymd(2016, 3, 1).and_hms(0, 0, 0),
+                is_bot: false,
+            },
+            FirstContribution {
+                repo_id: 1,
+                actor: "foo".to_string(),
+                first_contributed_at: Utc.ymd(2016, 2, 1).and_hms(0, 0, 0),
+                is_bot: false,
+            },
+        ];
+        items.sort();
+        items.dedup_by(|a, b| a.repo_id == b.repo_id && a.actor == b.actor);
+
+        let expected_sql = "INSERT INTO first_contributions (repo_id, actor_name, first_contributed_at)
+VALUES (1, 'foo', '2016-02-01 00:00:00 UTC')
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET first_contributed_at = excluded.first_contributed_at
+WHERE first_contributions.first_contributed_at > EXCLUDED.first_contributed_at;";
+        assert_eq!(expected_sql, group_first_contribution_sql_insert_par(&items, false, false));
+
+        let expected_flagged_sql = "INSERT INTO first_contributions (repo_id, actor_name, first_contributed_at, is_bot)
+VALUES (1, 'foo', '2016-02-01 00:00:00 UTC', false)
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET first_contributed_at = LEAST(first_contributions.first_contributed_at, excluded.first_contributed_at),
+is_bot = first_contributions.is_bot OR excluded.is_bot;";
+        assert_eq!(expected_flagged_sql, group_first_contribution_sql_insert_par(&items, false, true));
+    }
+
+    #[test]
+    fn multi_row_insert_releases() {
+        use rusty_von_humboldt::types::ReleaseActivity;
+        use rusty_von_humboldt::releases::ReleaseCadence;
+        use chrono::{TimeZone, Utc};
+        use {group_release_cadence_sql_insert, group_release_sql_insert_par};
+
+        let activities = vec![
+            ReleaseActivity {
+                event_id: 99,
+                repo_id: 1,
+                kind: "tag".to_string(),
+                action: "created".to_string(),
+                name: "v1.0".to_string(),
+                prerelease: false,
+                actor: "foo".to_string(),
+                happened_at: Utc.ymd(2016, 1, 1).and_hms(1, 0, 0),
+            },
+        ];
+        let expected_sql = "INSERT INTO repo_releases (event_id, repo_id, kind, action, name, prerelease, actor_name, happened_at) VALUES (99, 1, 'tag', 'created', 'v1.0', false, 'foo', '2016-01-01 01:00:00 UTC') ON CONFLICT (event_id) DO NOTHING;";
+        assert_eq!(expected_sql, group_release_sql_insert_par(&activities, false));
+
+        let stats = vec![
+            ReleaseCadence {
+                repo_id: 1,
+                releases: 1,
+                prereleases: 0,
+                first_release_at: Utc.ymd(2016, 1, 1).and_hms(1, 0, 0),
+                last_release_at: Utc.ymd(2016, 1, 1).and_hms(1, 0, 0),
+                mean_seconds_between: None,
+                median_seconds_between: None,
+            },
+        ];
+        let expected_stats_sql = "INSERT INTO repo_release_stats (repo_id, releases, prereleases, first_release_at, last_release_at, mean_seconds_between, median_seconds_between) VALUES (1, 1, 0, '2016-01-01 01:00:00 UTC', '2016-01-01 01:00:00 UTC', NULL, NULL)
+ON CONFLICT (repo_id) DO UPDATE SET (releases, prereleases, first_release_at, last_release_at, mean_seconds_between, median_seconds_between) = (excluded.releases, excluded.prereleases, excluded.first_release_at, excluded.last_release_at, excluded.mean_seconds_between, excluded.median_seconds_between);";
+        assert_eq!(expected_stats_sql, group_release_cadence_sql_insert(&stats));
+    }
+
+    #[test]
+    fn multi_row_insert_reviews() {
+        use rusty_von_humboldt::types::{CommitEvent, PrReview};
+        use chrono::{TimeZone, Utc};
+        use {group_repo_actor_sql_insert_par, group_review_sql_insert_par};
+
+        let reviews = vec![
+            PrReview {
+                event_id: 99,
+                repo_id: 1,
+                number: 7,
+                reviewer: "foo".to_string(),
+                state: "approved".to_string(),
+                submitted_at: Utc.ymd(2016, 1, 1).and_hms(1, 0, 0),
+                is_bot: false,
+            },
+        ];
+        let expected_sql = "INSERT INTO pr_reviews (event_id, repo_id, number, reviewer, state, submitted_at) VALUES (99, 1, 7, 'foo', 'approved', '2016-01-01 01:00:00 UTC') ON CONFLICT (event_id) DO NOTHING;";
+        assert_eq!(expected_sql, group_review_sql_insert_par(&reviews, false));
+
+        let reviewers = vec![
+            CommitEvent {
+                actor: "foo".to_string(),
+                repo_id: 1,
+                period: None,
+                is_bot: true,
+            },
+        ];
+        let expected_reviewers_sql = "INSERT INTO reviewer_repo_id_names (repo_id, actor_name, is_bot) VALUES (1, 'foo', true)
+ON CONFLICT (repo_id, actor_name) DO UPDATE SET is_bot = reviewer_repo_id_names.is_bot OR excluded.is_bot;";
+        assert_eq!(
+            expected_reviewers_sql,
+            group_repo_actor_sql_insert_par("reviewer_repo_id_names", &reviewers, false, true, false)
         );
     }
 
+    #[test]
+    fn raw_lines_split_by_partition() {
+        use split_by_partition;
+
+        let lines = vec![
+            ("1".to_string(), "a".to_string()),
+            ("1".to_string(), "b".to_string()),
+            ("2".to_string(), "c".to_string()),
+        ];
+        let partitions = split_by_partition(&lines);
+        assert_eq!(2, partitions.len());
+        assert_eq!(2, partitions[0].len());
+        assert_eq!("c", partitions[1][0].1);
+        assert!(split_by_partition::<String>(&[]).is_empty());
+    }
+
+    #[test]
+    fn multi_row_insert_orgs() {
+        use rusty_von_humboldt::types::OrgEventCount;
+        use {group_org_committers_sql_insert, group_org_event_counts_sql_insert, group_org_repos_sql_insert};
+
+        let counts = vec![
+            OrgEventCount {
+                org: "foo".to_string(),
+                event_type: "PushEvent".to_string(),
+                events: 12,
+            },
+        ];
+        let expected_counts_sql = "INSERT INTO org_event_counts (org, event_type, events) VALUES ('foo', 'PushEvent', 12)
+ON CONFLICT (org, event_type) DO UPDATE SET events = excluded.events;";
+        assert_eq!(expected_counts_sql, group_org_event_counts_sql_insert(&counts));
+
+        let expected_repos_sql = "INSERT INTO org_repos (org, repo_id) VALUES ('foo', 1), ('foo', 2) ON CONFLICT DO NOTHING;";
+        assert_eq!(expected_repos_sql, group_org_repos_sql_insert(&[("foo", 1), ("foo", 2)]));
+
+        let expected_committers_sql = "INSERT INTO org_committers (org, actor_name) VALUES ('foo', 'bar') ON CONFLICT DO NOTHING;";
+        assert_eq!(expected_committers_sql, group_org_committers_sql_insert(&[("foo", "bar", true)], false, false));
+        let expected_flagged_sql = "INSERT INTO org_committers (org, actor_name, is_bot) VALUES ('foo', 'bar', true)
+ON CONFLICT (org, actor_name) DO UPDATE SET is_bot = org_committers.is_bot OR excluded.is_bot;";
+        assert_eq!(expected_flagged_sql, group_org_committers_sql_insert(&[("foo", "bar", true)], false, true));
+    }
+
     // Put multiple rows into a single INSERT statement, with ON CONFLICT clause
     #[test]
     fn multi_row_insert_sql() {
@@ -0,0 +1,284 @@
+//! Records as Parquet files, written with the `parquet` crate.  Every column is optional and gzipped.
+
+extern crate parquet;
+
+use std::sync::Arc;
+use chrono::NaiveDate;
+use self::parquet::basic::{Compression, GzipLevel, LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use self::parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type};
+use self::parquet::file::properties::WriterProperties;
+use self::parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use self::parquet::format::MilliSeconds;
+use self::parquet::schema::types::Type;
+use records::*;
+
+/// A Parquet file of the records, with at most `row_group_size` rows in each row group.
+pub fn parquet_file<R: Record>(records: &[R], row_group_size: usize) -> Vec<u8> {
+    let rows: Vec<Vec<Value>> = records.iter().map(|record| record.values()).collect();
+    parquet_table(&R::columns(), &rows, row_group_size)
+}
+
+/// A Parquet file of rows that have a value for each of the columns.
+pub fn parquet_table(columns: &[Column], rows: &[Vec<Value>], row_group_size: usize) -> Vec<u8> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::GZIP(GzipLevel::default()))
+        .set_created_by("rusty-von-humboldt".to_string())
+        .build();
+    let mut writer = SerializedFileWriter::new(Vec::new(), Arc::new(schema(columns)), Arc::new(properties))
+        .expect("Couldn't start Parquet file");
+
+    for values in rows.chunks(row_group_size.max(1)) {
+        let mut row_group = writer.next_row_group().expect("Couldn't start row group");
+        for (index, column) in columns.iter().enumerate() {
+            let mut column_writer = row_group
+                .next_column()
+                .expect("Couldn't start column")
+                .expect("Parquet schema is missing a column");
+            write_column(&mut column_writer, column.column_type, values.iter().map(|row| &row[index]));
+            column_writer.close().expect("Couldn't write column");
+        }
+        row_group.close().expect("Couldn't write row group");
+    }
+
+    writer.into_inner().expect("Couldn't finish Parquet file")
+}
+
+fn schema(columns: &[Column]) -> Type {
+    let fields = columns
+        .iter()
+        .map(|column| {
+            let (physical_type, logical_type) = match column.column_type {
+                ColumnType::Int64 => (PhysicalType::INT64, None),
+                ColumnType::Boolean => (PhysicalType::BOOLEAN, None),
+                ColumnType::Utf8 => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+                ColumnType::Timestamp => (
+                    PhysicalType::INT64,
+                    Some(LogicalType::Timestamp {
+                        is_adjusted_to_u_t_c: true,
+                        unit: TimeUnit::MILLIS(MilliSeconds {}),
+                    }),
+                ),
+                ColumnType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+            };
+            Arc::new(
+                Type::primitive_type_builder(column.name, physical_type)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .with_logical_type(logical_type)
+                    .build()
+                    .expect("Bad Parquet column"),
+            )
+        })
+        .collect();
+    Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()
+        .expect("Bad Parquet schema")
+}
+
+/// Write a row group's values for a column: a definition level for every row, 0 for nulls, and the other values.
+fn write_column<'a, I>(writer: &mut SerializedColumnWriter, column_type: ColumnType, values: I)
+where
+    I: Iterator<Item = &'a Value>,
+{
+    let values: Vec<&Value> = values.collect();
+    let levels: Vec<i16> = values
+        .iter()
+        .map(|value| match **value {
+            Value::Null => 0,
+            _ => 1,
+        })
+        .collect();
+    let present = values.iter().filter(|value| ***value != Value::Null);
+    let mismatch = |value: &Value| -> ! { panic!("{:?} in a {:?} column", value, column_type) };
+
+    let written = match column_type {
+        ColumnType::Int64 | ColumnType::Timestamp => {
+            let numbers: Vec<i64> = present
+                .map(|value| match (column_type, *value) {
+                    (ColumnType::Int64, &Value::Int64(number)) => number,
+                    (ColumnType::Timestamp, &Value::Timestamp(time)) => time.timestamp_millis(),
+                    (_, value) => mismatch(value),
+                })
+                .collect();
+            writer.typed::<Int64Type>().write_batch(&numbers, Some(&levels), None)
+        }
+        ColumnType::Date => {
+            let days: Vec<i32> = present
+                .map(|value| match **value {
+                    Value::Date(date) => date.signed_duration_since(NaiveDate::from_ymd(1970, 1, 1)).num_days() as i32,
+                    ref value => mismatch(value),
+                })
+                .collect();
+            writer.typed::<Int32Type>().write_batch(&days, Some(&levels), None)
+        }
+        ColumnType::Utf8 => {
+            let texts: Vec<ByteArray> = present
+                .map(|value| match **value {
+                    Value::Utf8(ref text) => ByteArray::from(text.as_str()),
+                    ref value => mismatch(value),
+                })
+                .collect();
+            writer.typed::<ByteArrayType>().write_batch(&texts, Some(&levels), None)
+        }
+        ColumnType::Boolean => {
+            let flags: Vec<bool> = present
+                .map(|value| match **value {
+                    Value::Boolean(flag) => flag,
+                    ref value => mismatch(value),
+                })
+                .collect();
+            writer.typed::<BoolType>().write_batch(&flags, Some(&levels), None)
+        }
+    };
+    written.expect("Couldn't write Parquet values");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use parquet::{parquet_file, parquet_table};
+    use parquet::parquet::basic::Compression;
+    use parquet::parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::parquet::record::Field;
+    use records::*;
+    use types::CommitEvent;
+
+    /// Read a file back with the `parquet` crate's reader: column names, codecs, rows in each row group and rows.
+    fn read_parquet(file: &[u8], name: &str) -> (Vec<String>, Vec<Compression>, Vec<i64>, Vec<Vec<Value>>) {
+        let path = env::temp_dir().join(name);
+        File::create(&path).unwrap().write_all(file).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+
+        let metadata = reader.metadata();
+        let names = metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        let codecs = metadata
+            .row_groups()
+            .iter()
+            .flat_map(|row_group| row_group.columns().iter().map(|column| column.compression()))
+            .collect();
+        let group_rows = metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.num_rows())
+            .collect();
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(_, field)| match *field {
+                        Field::Null => Value::Null,
+                        Field::Long(number) => Value::Int64(number),
+                        Field::Bool(flag) => Value::Boolean(flag),
+                        Field::Str(ref text) => Value::Utf8(text.clone()),
+                        Field::TimestampMillis(millis) => Value::Timestamp(Utc.timestamp_millis(millis)),
+                        Field::Date(days) => Value::Date(NaiveDate::from_ymd(1970, 1, 1) + Duration::days(days as i64)),
+                        ref field => panic!("Unexpected {:?}", field),
+                    })
+                    .collect()
+            })
+            .collect();
+        fs::remove_file(&path).unwrap();
+        (names, codecs, group_rows, rows)
+    }
+
+    #[test]
+    fn round_trip() {
+        let columns = vec![
+            Column {
+                name: "id",
+                column_type: ColumnType::Int64,
+                actor: false,
+            },
+            Column {
+                name: "flag",
+                column_type: ColumnType::Boolean,
+                actor: false,
+            },
+            Column {
+                name: "name",
+                column_type: ColumnType::Utf8,
+                actor: true,
+            },
+            Column {
+                name: "at",
+                column_type: ColumnType::Timestamp,
+                actor: false,
+            },
+            Column {
+                name: "on",
+                column_type: ColumnType::Date,
+                actor: false,
+            },
+        ];
+        let rows: Vec<Vec<Value>> = (0..21)
+            .map(|index| {
+                vec![
+                    Value::Int64(index - 10),
+                    if index % 5 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Boolean(index % 3 == 0)
+                    },
+                    if index % 4 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Utf8(format!("naïve {}", index))
+                    },
+                    Value::Timestamp(Utc.ymd(2016, 1, 2).and_hms(3, 4, 5) + Duration::milliseconds(index * 1001)),
+                    if index < 7 {
+                        Value::Null
+                    } else {
+                        Value::Date(NaiveDate::from_ymd(1969, 12, 25) + Duration::days(index))
+                    },
+                ]
+            })
+            .collect();
+
+        let (names, codecs, group_rows, read) =
+            read_parquet(&parquet_table(&columns, &rows, 9), "rvh-parquet-test.parquet");
+        assert_eq!(vec!["id", "flag", "name", "at", "on"], names);
+        assert!(codecs.iter().all(|codec| match *codec {
+            Compression::GZIP(_) => true,
+            _ => false,
+        }));
+        assert_eq!(vec![9, 9, 3], group_rows);
+        assert_eq!(rows, read);
+
+        let (names, _, group_rows, read) =
+            read_parquet(&parquet_table(&columns, &[], 9), "rvh-parquet-empty-test.parquet");
+        assert_eq!(5, names.len());
+        assert!(group_rows.is_empty());
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn records() {
+        let committers: Vec<CommitEvent> = (0..5)
+            .map(|repo_id| CommitEvent {
+                actor: "foo".to_string(),
+                repo_id: repo_id,
+                period: None,
+                is_bot: repo_id % 2 == 0,
+            })
+            .collect();
+
+        let (names, _, group_rows, read) =
+            read_parquet(&parquet_file(&committers, 2), "rvh-parquet-records-test.parquet");
+        let columns: Vec<String> = CommitEvent::columns().iter().map(|column| column.name.to_string()).collect();
+        assert_eq!(columns, names);
+        assert_eq!(vec![2, 2, 1], group_rows);
+        let values: Vec<Vec<Value>> = committers.iter().map(|committer| committer.values()).collect();
+        assert_eq!(values, read);
+    }
+}
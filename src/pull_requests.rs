@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use types::*;
+
+/// How long a repo's merged pull requests took to merge, in seconds from being opened.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct MergeStats {
+    pub repo_id: i64,
+    pub merged_prs: usize,
+    pub mean_seconds: i64,
+    pub median_seconds: i64,
+    pub p90_seconds: i64,
+}
+
+/// Time to merge statistics for each repo with merged pull requests.
+/// The same pull request can be in here more than once, it's only counted once.
+pub fn merge_stats(prs: &[PrLifecycle]) -> Vec<MergeStats> {
+    let mut merge_times: HashMap<i64, HashMap<i64, i64>> = HashMap::new();
+    for pr in prs {
+        if let (Some(opened_at), Some(merged_at)) = (pr.opened_at, pr.merged_at) {
+            merge_times
+                .entry(pr.repo_id)
+                .or_insert_with(HashMap::new)
+                .insert(pr.number, merged_at.signed_duration_since(opened_at).num_seconds());
+        }
+    }
+
+    let mut stats: Vec<MergeStats> = merge_times
+        .into_iter()
+        .map(|(repo_id, by_number)| {
+            let mut seconds: Vec<i64> = by_number.into_iter().map(|(_, seconds)| seconds).collect();
+            seconds.sort();
+            MergeStats {
+                repo_id: repo_id,
+                merged_prs: seconds.len(),
+                mean_seconds: seconds.iter().sum::<i64>() / seconds.len() as i64,
+                median_seconds: percentile(&seconds, 50),
+                p90_seconds: percentile(&seconds, 90),
+            }
+        })
+        .collect();
+    stats.sort();
+    stats
+}
+
+/// Nearest rank percentile of sorted values.
+fn percentile(sorted: &[i64], percent: usize) -> i64 {
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use pull_requests::merge_stats;
+    use types::PrLifecycle;
+
+    #[test]
+    fn time_to_merge() {
+        let opened_at = Utc.ymd(2016, 1, 1).and_hms(0, 0, 0);
+        let prs: Vec<PrLifecycle> = [
+            (1, 1, Some(1)),
+            // same PR seen twice:
+            (1, 1, Some(1)),
+            (1, 2, Some(2)),
+            (1, 3, Some(9)),
+            // never merged:
+            (1, 4, None),
+            (2, 1, Some(24)),
+        ].iter()
+            .map(|&(repo_id, number, hours_to_merge)| PrLifecycle {
+                repo_id: repo_id,
+                number: number,
+                author: "someone".to_string(),
+                opened_at: Some(opened_at),
+                closed_at: hours_to_merge.map(Duration::hours).map(|time_to_merge| opened_at + time_to_merge),
+                merged_at: hours_to_merge.map(Duration::hours).map(|time_to_merge| opened_at + time_to_merge),
+                additions: None,
+                deletions: None,
+                base_branch: "master".to_string(),
+                cross_repo: false,
+                updated_at: opened_at,
+            })
+            .collect();
+        let stats = merge_stats(&prs);
+
+        assert_eq!(2, stats.len());
+        assert_eq!(1, stats[0].repo_id);
+        assert_eq!(3, stats[0].merged_prs);
+        assert_eq!(4 * 3600, stats[0].mean_seconds);
+        assert_eq!(2 * 3600, stats[0].median_seconds);
+        assert_eq!(9 * 3600, stats[0].p90_seconds);
+        assert_eq!(24 * 3600, stats[1].median_seconds);
+    }
+}
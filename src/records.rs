@@ -0,0 +1,594 @@
+use std::str::FromStr;
+use chrono::{DateTime, NaiveDate, Utc};
+use types::*;
+use forks::ForkStats;
+use issues::IssueStats;
+use pull_requests::MergeStats;
+use releases::ReleaseCadence;
+use stars::{DailyStars, StarStats};
+
+/// What results are written as.  SQL is loaded into Postgres, Parquet is for Spark, DuckDB and the like,
+/// CSV and JSON lines are for everything else and SQLite is a database file for a laptop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Sql,
+    Parquet,
+    Csv,
+    Jsonl,
+    Sqlite,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "sql" => Ok(OutputFormat::Sql),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "sqlite" => Ok(OutputFormat::Sqlite),
+            _ => Err(format!("Unknown output format {}, should be sql, parquet, csv, jsonl or sqlite", s)),
+        }
+    }
+}
+
+/// Type of a column of output.  Timestamps are UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Int64,
+    Boolean,
+    Utf8,
+    Timestamp,
+    Date,
+}
+
+/// A column of output.  `actor` columns hold account names, which are obfuscated along with committers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Column {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(skip)]
+    pub actor: bool,
+}
+
+/// A value in a column.  Any column can be null.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int64(i64),
+    Boolean(bool),
+    Utf8(String),
+    Timestamp(DateTime<Utc>),
+    Date(NaiveDate),
+}
+
+/// Something that can be written out as a row of typed columns.
+/// `values` has a value for each of the `columns`, in the same order.
+pub trait Record {
+    fn columns() -> Vec<Column>;
+    fn values(&self) -> Vec<Value>;
+}
+
+fn column(name: &'static str, column_type: ColumnType) -> Column {
+    Column {
+        name: name,
+        column_type: column_type,
+        actor: false,
+    }
+}
+
+fn actor_column(name: &'static str) -> Column {
+    Column {
+        name: name,
+        column_type: ColumnType::Utf8,
+        actor: true,
+    }
+}
+
+fn optional_utf8(value: &Option<String>) -> Value {
+    match *value {
+        Some(ref value) => Value::Utf8(value.clone()),
+        None => Value::Null,
+    }
+}
+
+fn optional_int64(value: Option<i64>) -> Value {
+    match value {
+        Some(value) => Value::Int64(value),
+        None => Value::Null,
+    }
+}
+
+fn optional_timestamp(value: Option<DateTime<Utc>>) -> Value {
+    match value {
+        Some(value) => Value::Timestamp(value),
+        None => Value::Null,
+    }
+}
+
+/// Committer rows.  Period is null unless committers are counted per period.
+impl Record for CommitEvent {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            actor_column("actor_name"),
+            column("period", ColumnType::Date),
+            column("is_bot", ColumnType::Boolean),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.actor.clone()),
+            match self.period {
+                Some(period) => Value::Date(period),
+                None => Value::Null,
+            },
+            Value::Boolean(self.is_bot),
+        ]
+    }
+}
+
+impl Record for RepoIdToName {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("repo_name", ColumnType::Utf8),
+            column("event_timestamp", ColumnType::Timestamp),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.repo_name.clone()),
+            Value::Timestamp(self.event_timestamp),
+        ]
+    }
+}
+
+impl Record for AuthoredCommit {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("sha", ColumnType::Utf8),
+            actor_column("author"),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.sha.clone()),
+            Value::Utf8(self.author.clone()),
+        ]
+    }
+}
+
+impl Record for CommitCount {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            actor_column("author"),
+            column("period", ColumnType::Date),
+            column("commits", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.author.clone()),
+            Value::Date(self.period),
+            Value::Int64(self.commits),
+        ]
+    }
+}
+
+impl Record for DailyStars {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("starred_on", ColumnType::Date),
+            column("stars", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Date(self.starred_on),
+            Value::Int64(self.stars as i64),
+        ]
+    }
+}
+
+impl Record for StarStats {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("stargazers", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![Value::Int64(self.repo_id), Value::Int64(self.stargazers as i64)]
+    }
+}
+
+/// Fork owners are part of the fork's public name so they aren't actor columns.
+impl Record for ForkEvent {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("parent_repo_id", ColumnType::Int64),
+            column("fork_repo_id", ColumnType::Int64),
+            column("fork_owner", ColumnType::Utf8),
+            column("created_at", ColumnType::Timestamp),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.parent_repo_id),
+            Value::Int64(self.fork_repo_id),
+            Value::Utf8(self.fork_owner.clone()),
+            Value::Timestamp(self.created_at),
+        ]
+    }
+}
+
+impl Record for ForkStats {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("fork_count", ColumnType::Int64),
+            column("depth", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Int64(self.fork_count as i64),
+            Value::Int64(self.depth as i64),
+        ]
+    }
+}
+
+impl Record for PrLifecycle {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("number", ColumnType::Int64),
+            actor_column("author"),
+            column("opened_at", ColumnType::Timestamp),
+            column("closed_at", ColumnType::Timestamp),
+            column("merged_at", ColumnType::Timestamp),
+            column("additions", ColumnType::Int64),
+            column("deletions", ColumnType::Int64),
+            column("base_branch", ColumnType::Utf8),
+            column("cross_repo", ColumnType::Boolean),
+            column("updated_at", ColumnType::Timestamp),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Int64(self.number),
+            Value::Utf8(self.author.clone()),
+            optional_timestamp(self.opened_at),
+            optional_timestamp(self.closed_at),
+            optional_timestamp(self.merged_at),
+            optional_int64(self.additions),
+            optional_int64(self.deletions),
+            Value::Utf8(self.base_branch.clone()),
+            Value::Boolean(self.cross_repo),
+            Value::Timestamp(self.updated_at),
+        ]
+    }
+}
+
+impl Record for MergeStats {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("merged_prs", ColumnType::Int64),
+            column("mean_seconds", ColumnType::Int64),
+            column("median_seconds", ColumnType::Int64),
+            column("p90_seconds", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Int64(self.merged_prs as i64),
+            Value::Int64(self.mean_seconds),
+            Value::Int64(self.median_seconds),
+            Value::Int64(self.p90_seconds),
+        ]
+    }
+}
+
+impl Record for IssueActivity {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("event_id", ColumnType::Int64),
+            column("repo_id", ColumnType::Int64),
+            column("number", ColumnType::Int64),
+            column("action", ColumnType::Utf8),
+            actor_column("actor_name"),
+            column("label", ColumnType::Utf8),
+            column("happened_at", ColumnType::Timestamp),
+            actor_column("issue_author"),
+            column("opened_at", ColumnType::Timestamp),
+            column("is_pull_request", ColumnType::Boolean),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.event_id),
+            Value::Int64(self.repo_id),
+            Value::Int64(self.number),
+            Value::Utf8(self.action.clone()),
+            Value::Utf8(self.actor.clone()),
+            Value::Utf8(self.label.clone()),
+            Value::Timestamp(self.happened_at),
+            Value::Utf8(self.issue_author.clone()),
+            optional_timestamp(self.opened_at),
+            Value::Boolean(self.is_pull_request),
+        ]
+    }
+}
+
+impl Record for IssueStats {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("issues_opened", ColumnType::Int64),
+            column("issues_closed", ColumnType::Int64),
+            column("comments", ColumnType::Int64),
+            column("responded_issues", ColumnType::Int64),
+            column("median_first_response_seconds", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Int64(self.issues_opened as i64),
+            Value::Int64(self.issues_closed as i64),
+            Value::Int64(self.comments as i64),
+            Value::Int64(self.responded_issues as i64),
+            optional_int64(self.median_first_response_seconds),
+        ]
+    }
+}
+
+impl Record for FirstContribution {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            actor_column("actor_name"),
+            column("first_contributed_at", ColumnType::Timestamp),
+            column("is_bot", ColumnType::Boolean),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.actor.clone()),
+            Value::Timestamp(self.first_contributed_at),
+            Value::Boolean(self.is_bot),
+        ]
+    }
+}
+
+impl Record for ReleaseActivity {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("event_id", ColumnType::Int64),
+            column("repo_id", ColumnType::Int64),
+            column("kind", ColumnType::Utf8),
+            column("action", ColumnType::Utf8),
+            column("name", ColumnType::Utf8),
+            column("prerelease", ColumnType::Boolean),
+            actor_column("actor_name"),
+            column("happened_at", ColumnType::Timestamp),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.event_id),
+            Value::Int64(self.repo_id),
+            Value::Utf8(self.kind.clone()),
+            Value::Utf8(self.action.clone()),
+            Value::Utf8(self.name.clone()),
+            Value::Boolean(self.prerelease),
+            Value::Utf8(self.actor.clone()),
+            Value::Timestamp(self.happened_at),
+        ]
+    }
+}
+
+impl Record for ReleaseCadence {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("repo_id", ColumnType::Int64),
+            column("releases", ColumnType::Int64),
+            column("prereleases", ColumnType::Int64),
+            column("first_release_at", ColumnType::Timestamp),
+            column("last_release_at", ColumnType::Timestamp),
+            column("mean_seconds_between", ColumnType::Int64),
+            column("median_seconds_between", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.repo_id),
+            Value::Int64(self.releases as i64),
+            Value::Int64(self.prereleases as i64),
+            Value::Timestamp(self.first_release_at),
+            Value::Timestamp(self.last_release_at),
+            optional_int64(self.mean_seconds_between),
+            optional_int64(self.median_seconds_between),
+        ]
+    }
+}
+
+/// Reviews leave out `is_bot`, which goes with the reviewer in `reviewer_repo_id_names` like it does in the SQL.
+impl Record for PrReview {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("event_id", ColumnType::Int64),
+            column("repo_id", ColumnType::Int64),
+            column("number", ColumnType::Int64),
+            actor_column("reviewer"),
+            column("state", ColumnType::Utf8),
+            column("submitted_at", ColumnType::Timestamp),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Int64(self.event_id),
+            Value::Int64(self.repo_id),
+            Value::Int64(self.number),
+            Value::Utf8(self.reviewer.clone()),
+            Value::Utf8(self.state.clone()),
+            Value::Timestamp(self.submitted_at),
+        ]
+    }
+}
+
+impl Record for OrgEventCount {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("org", ColumnType::Utf8),
+            column("event_type", ColumnType::Utf8),
+            column("events", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Utf8(self.org.clone()),
+            Value::Utf8(self.event_type.clone()),
+            Value::Int64(self.events as i64),
+        ]
+    }
+}
+
+/// The orgs mode's org and repo ID pairs.
+impl<'a> Record for (&'a str, i64) {
+    fn columns() -> Vec<Column> {
+        vec![column("org", ColumnType::Utf8), column("repo_id", ColumnType::Int64)]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![Value::Utf8(self.0.to_string()), Value::Int64(self.1)]
+    }
+}
+
+/// The orgs mode's org and committer pairs, with if the committer is a bot.
+impl<'a> Record for (&'a str, &'a str, bool) {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("org", ColumnType::Utf8),
+            actor_column("actor_name"),
+            column("is_bot", ColumnType::Boolean),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::Utf8(self.0.to_string()),
+            Value::Utf8(self.1.to_string()),
+            Value::Boolean(self.2),
+        ]
+    }
+}
+
+/// Events flattened to the fields most questions need.  Payload fields are null when the event doesn't have them.
+impl Record for Event {
+    fn columns() -> Vec<Column> {
+        vec![
+            column("id", ColumnType::Int64),
+            column("event_type", ColumnType::Utf8),
+            column("created_at", ColumnType::Timestamp),
+            column("actor_id", ColumnType::Int64),
+            column("actor_login", ColumnType::Utf8),
+            column("repo_id", ColumnType::Int64),
+            column("repo_name", ColumnType::Utf8),
+            column("org_id", ColumnType::Int64),
+            column("org_login", ColumnType::Utf8),
+            column("action", ColumnType::Utf8),
+            column("ref", ColumnType::Utf8),
+            column("ref_type", ColumnType::Utf8),
+            column("pr_number", ColumnType::Int64),
+            column("issue_number", ColumnType::Int64),
+            column("push_size", ColumnType::Int64),
+            column("push_distinct_size", ColumnType::Int64),
+        ]
+    }
+
+    fn values(&self) -> Vec<Value> {
+        let no_payload = Payload::default();
+        let payload = self.payload.as_ref().unwrap_or(&no_payload);
+        vec![
+            // pre-2015 events don't have IDs
+            match self.id {
+                -1 => Value::Null,
+                id => Value::Int64(id),
+            },
+            Value::Utf8(self.event_type.clone()),
+            Value::Timestamp(self.created_at),
+            match self.actor.id {
+                -1 => Value::Null,
+                id => Value::Int64(id),
+            },
+            optional_utf8(&self.actor.login),
+            Value::Int64(self.repo.id),
+            Value::Utf8(self.repo.name.clone()),
+            optional_int64(self.org.as_ref().map(|org| org.id)),
+            optional_utf8(&self.org.as_ref().and_then(|org| org.login.clone())),
+            optional_utf8(&payload.action),
+            optional_utf8(&payload.ref_name),
+            optional_utf8(&payload.ref_type),
+            optional_int64(payload.pull_request.as_ref().and_then(|pr| pr.number)),
+            optional_int64(payload.issue.as_ref().and_then(|issue| issue.number)),
+            optional_int64(payload.size),
+            optional_int64(payload.distinct_size),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use records::{Record, Value};
+    use types::Event;
+
+    #[test]
+    fn flattened_event() {
+        let mut event = Event::new();
+        event.id = 12;
+        event.event_type = "PushEvent".to_string();
+
+        let values = event.values();
+        assert_eq!(Event::columns().len(), values.len());
+        assert_eq!(Value::Int64(12), values[0]);
+        assert_eq!(Value::Utf8("PushEvent".to_string()), values[1]);
+        // the placeholder's actor ID:
+        assert_eq!(Value::Null, values[3]);
+        assert_eq!(Value::Null, values[9]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use types::*;
+
+/// How often a repo ships.  A release is a published, non prerelease GitHub release, or for repos that
+/// never publish releases, a tag being created.  Times between releases are in seconds.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct ReleaseCadence {
+    pub repo_id: i64,
+    pub releases: usize,
+    pub prereleases: usize,
+    pub first_release_at: DateTime<Utc>,
+    pub last_release_at: DateTime<Utc>,
+    pub mean_seconds_between: Option<i64>,
+    pub median_seconds_between: Option<i64>,
+}
+
+/// What we've seen of one repo's releases and tags, by name with the earliest time for each.
+#[derive(Debug, Clone, Default)]
+struct RepoReleases<'a> {
+    releases: HashMap<&'a str, DateTime<Utc>>,
+    prereleases: HashMap<&'a str, DateTime<Utc>>,
+    tags: HashMap<&'a str, DateTime<Utc>>,
+}
+
+fn add_earliest<'a>(times: &mut HashMap<&'a str, DateTime<Utc>>, name: &'a str, time: DateTime<Utc>) {
+    let earliest = times.entry(name).or_insert(time);
+    if time < *earliest {
+        *earliest = time;
+    }
+}
+
+/// Release cadence for each repo with at least one release or tag.
+/// The same tag or release in here more than once is only counted once.
+pub fn release_cadence(activities: &[ReleaseActivity]) -> Vec<ReleaseCadence> {
+    let mut by_repo: HashMap<i64, RepoReleases> = HashMap::new();
+    for activity in activities {
+        let repo = by_repo.entry(activity.repo_id).or_insert_with(RepoReleases::default);
+        match (activity.kind.as_ref(), activity.action.as_ref()) {
+            ("release", "published") => if activity.prerelease {
+                add_earliest(&mut repo.prereleases, &activity.name, activity.happened_at);
+            } else {
+                add_earliest(&mut repo.releases, &activity.name, activity.happened_at);
+            },
+            ("tag", "created") => add_earliest(&mut repo.tags, &activity.name, activity.happened_at),
+            _ => (),
+        }
+    }
+
+    let mut cadences: Vec<ReleaseCadence> = by_repo
+        .into_iter()
+        .filter_map(|(repo_id, repo)| {
+            let release_times = match repo.releases.is_empty() {
+                true => &repo.tags,
+                false => &repo.releases,
+            };
+            let mut times: Vec<DateTime<Utc>> = release_times.values().cloned().collect();
+            if times.is_empty() {
+                return None;
+            }
+            times.sort();
+            let mut gaps: Vec<i64> = times
+                .windows(2)
+                .map(|pair| pair[1].signed_duration_since(pair[0]).num_seconds())
+                .collect();
+            gaps.sort();
+            Some(ReleaseCadence {
+                repo_id: repo_id,
+                releases: times.len(),
+                prereleases: repo.prereleases.len(),
+                first_release_at: times[0],
+                last_release_at: times[times.len() - 1],
+                mean_seconds_between: match gaps.is_empty() {
+                    true => None,
+                    false => Some(gaps.iter().sum::<i64>() / gaps.len() as i64),
+                },
+                median_seconds_between: match gaps.is_empty() {
+                    true => None,
+                    false => Some(gaps[(gaps.len() - 1) / 2]),
+                },
+            })
+        })
+        .collect();
+    cadences.sort();
+    cadences
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use releases::release_cadence;
+    use types::ReleaseActivity;
+
+    #[test]
+    fn cadence() {
+        let activities: Vec<ReleaseActivity> = [
+            (1, "tag", "v1", false, 1),
+            (1, "release", "v1", false, 1),
+            (1, "release", "v2-rc1", true, 2),
+            (1, "release", "v2", false, 3),
+            // seen again later:
+            (1, "release", "v2", false, 9),
+            (1, "release", "v3", false, 9),
+            // only tags, so tags are the releases:
+            (2, "tag", "0.1", false, 1),
+            (2, "tag", "0.2", false, 11),
+            // branches aren't releases:
+            (3, "branch", "feature", false, 1),
+        ].iter()
+            .map(|&(repo_id, kind, name, prerelease, day)| ReleaseActivity {
+                event_id: 1,
+                repo_id: repo_id,
+                kind: kind.to_string(),
+                action: match kind {
+                    "release" => "published".to_string(),
+                    _ => "created".to_string(),
+                },
+                name: name.to_string(),
+                prerelease: prerelease,
+                actor: "maintainer".to_string(),
+                happened_at: Utc.ymd(2016, 1, day).and_hms(0, 0, 0),
+            })
+            .collect();
+        let cadences = release_cadence(&activities);
+
+        assert_eq!(2, cadences.len());
+        assert_eq!(1, cadences[0].repo_id);
+        assert_eq!(3, cadences[0].releases);
+        assert_eq!(1, cadences[0].prereleases);
+        assert_eq!(Utc.ymd(2016, 1, 9).and_hms(0, 0, 0), cadences[0].last_release_at);
+        assert_eq!(Some(4 * 86400), cadences[0].mean_seconds_between);
+        assert_eq!(Some(2 * 86400), cadences[0].median_seconds_between);
+        assert_eq!(2, cadences[1].releases);
+        assert_eq!(Some(10 * 86400), cadences[1].median_seconds_between);
+    }
+}
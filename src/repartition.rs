@@ -0,0 +1,54 @@
+use std::env;
+use types::*;
+
+/// How the repartition mode splits up events.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Partitioning {
+    /// Into this many shards by repo ID, so all of a repo's events are in one shard.
+    Repo(u64),
+    /// By the UTC day the event happened.
+    Date,
+}
+
+impl Partitioning {
+    /// Read REPARTITION_BY (`repo` or `date`, `repo` if it isn't set) and REPARTITION_SHARDS (64 if it isn't set).
+    pub fn from_env() -> Partitioning {
+        match env::var("REPARTITION_BY") {
+            Ok(ref by) if by == "date" => Partitioning::Date,
+            Ok(ref by) if by != "repo" => panic!("Please set REPARTITION_BY to repo or date, got {}", by),
+            _ => Partitioning::Repo(match env::var("REPARTITION_SHARDS") {
+                Ok(shards) => match shards.parse::<u64>() {
+                    Ok(shards) if shards > 0 => shards,
+                    _ => panic!("Please set REPARTITION_SHARDS to a positive integer value"),
+                },
+                Err(_) => 64,
+            }),
+        }
+    }
+
+    /// Name of the partition the event goes in, used as a directory name.
+    pub fn partition(&self, event: &Event) -> String {
+        match *self {
+            Partitioning::Repo(shards) => format!("repo-{:04}", (event.repo.id as u64) % shards),
+            Partitioning::Date => format!("date-{}", event.created_at.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use repartition::Partitioning;
+    use types::Event;
+
+    #[test]
+    fn partitions() {
+        let mut event = Event::new();
+        event.repo.id = 131;
+        event.created_at = Utc.ymd(2016, 3, 4).and_hms(23, 59, 59);
+
+        assert_eq!("repo-0003", Partitioning::Repo(64).partition(&event));
+        assert_eq!("repo-0000", Partitioning::Repo(1).partition(&event));
+        assert_eq!("date-2016-03-04", Partitioning::Date.partition(&event));
+    }
+}
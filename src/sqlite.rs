@@ -0,0 +1,251 @@
+extern crate rusqlite;
+
+use std::collections::HashSet;
+use std::path::Path;
+use self::rusqlite::Connection;
+use self::rusqlite::types::Value as SqliteValue;
+use records::*;
+
+/// Writes output tables into a SQLite database, for analysis on a laptop.  Tables are made the first time
+/// rows go in to them, with the columns of the rows, and rows that are already there are handled the way
+/// the Postgres SQL handles them: the repo mapping keeps the newest name, committers are only added once, etc.
+pub struct SqliteSink {
+    connection: Connection,
+    tables: HashSet<String>,
+}
+
+/// What happens when a row with the same key is already in a table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conflict {
+    /// Keep the row that's there.
+    Ignore,
+    /// Take the new row if this column is later than the old row's.
+    KeepNewest(&'static str),
+    /// Take the new row if this column is earlier than the old row's.
+    KeepOldest(&'static str),
+    /// Take the new row.
+    Replace,
+}
+
+/// Key columns and what to do with rows that have a key that's already in the table.  Tables that aren't
+/// listed are keyed on the whole row, like `org_repos` that's inserted with `ON CONFLICT DO NOTHING`.
+/// Whatever the conflict, an `is_bot` column is ORed so a row flagged as a bot stays flagged.
+fn table_key(table: &str) -> Option<(Vec<&'static str>, Conflict)> {
+    match table {
+        "committer_repo_id_names" | "reviewer_repo_id_names" => {
+            Some((vec!["repo_id", "actor_name", "period"], Conflict::Ignore))
+        }
+        "org_committers" => Some((vec!["org", "actor_name"], Conflict::Ignore)),
+        "repo_mapping" => Some((vec!["repo_id"], Conflict::KeepNewest("event_timestamp"))),
+        "pull_requests" => Some((vec!["repo_id", "number"], Conflict::KeepNewest("updated_at"))),
+        "first_contributions" => Some((
+            vec!["repo_id", "actor_name"],
+            Conflict::KeepOldest("first_contributed_at"),
+        )),
+        "repo_commits" => Some((vec!["repo_id", "sha"], Conflict::Ignore)),
+        "repo_commit_counts" => Some((vec!["repo_id", "author", "period"], Conflict::Replace)),
+        "issue_events" | "repo_releases" | "pr_reviews" => Some((vec!["event_id"], Conflict::Ignore)),
+        "repo_daily_stars" => Some((vec!["repo_id", "starred_on"], Conflict::Replace)),
+        "repo_fork_stats" | "repo_merge_stats" | "repo_issue_stats" | "repo_release_stats" | "repo_star_stats" => {
+            Some((vec!["repo_id"], Conflict::Replace))
+        }
+        "org_event_counts" => Some((vec!["org", "event_type"], Conflict::Replace)),
+        _ => None,
+    }
+}
+
+impl SqliteSink {
+    /// Open the database, making it if it isn't there.
+    pub fn open(path: &Path) -> Result<SqliteSink, String> {
+        let connection = Connection::open(path).map_err(|e| format!("Couldn't open {}: {}", path.display(), e))?;
+        Ok(SqliteSink {
+            connection: connection,
+            tables: HashSet::new(),
+        })
+    }
+
+    /// Add rows to the table in one transaction, making the table if needed.
+    pub fn insert(&mut self, table: &str, columns: &[Column], rows: &[Vec<Value>]) -> Result<(), String> {
+        if !self.tables.contains(table) {
+            self.connection
+                .execute_batch(&create_table_sql(table, columns))
+                .map_err(|e| format!("Couldn't make table {}: {}", table, e))?;
+            self.tables.insert(table.to_string());
+        }
+
+        let sql = insert_sql(table, columns);
+        let transaction = self.connection
+            .transaction()
+            .map_err(|e| format!("Couldn't start a transaction: {}", e))?;
+        {
+            let mut statement = transaction
+                .prepare(&sql)
+                .map_err(|e| format!("Couldn't prepare insert into {}: {}", table, e))?;
+            for row in rows {
+                let values: Vec<SqliteValue> = row.iter().map(sqlite_value).collect();
+                statement
+                    .execute(&values)
+                    .map_err(|e| format!("Couldn't insert into {}: {}", table, e))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|e| format!("Couldn't commit to {}: {}", table, e))
+    }
+}
+
+fn create_table_sql(table: &str, columns: &[Column]) -> String {
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let column_type = match column.column_type {
+                ColumnType::Int64 | ColumnType::Boolean => "INTEGER",
+                ColumnType::Utf8 | ColumnType::Timestamp | ColumnType::Date => "TEXT",
+            };
+            format!("{} {}", column.name, column_type)
+        })
+        .collect();
+    match table_key(table) {
+        Some((key, _)) => format!(
+            "CREATE TABLE IF NOT EXISTS {table} ({});\nCREATE UNIQUE INDEX IF NOT EXISTS {table}_unique ON {table} ({});",
+            definitions.join(", "),
+            key_expressions(&key).join(", "),
+            table = table
+        ),
+        None => {
+            let key: Vec<&str> = columns.iter().map(|column| column.name).collect();
+            format!(
+                "CREATE TABLE IF NOT EXISTS {table} ({});\nCREATE UNIQUE INDEX IF NOT EXISTS {table}_key ON {table} ({});",
+                definitions.join(", "),
+                key_expressions(&key).join(", "),
+                table = table
+            )
+        }
+    }
+}
+
+/// Nulls are never equal in a unique index, so they're swapped for something that is.
+fn key_expressions(key: &[&str]) -> Vec<String> {
+    key.iter().map(|name| format!("ifnull({}, '')", name)).collect()
+}
+
+fn insert_sql(table: &str, columns: &[Column]) -> String {
+    let names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+    let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
+    let insert = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table,
+        names.join(", "),
+        placeholders.join(", ")
+    );
+    let (key, conflict) = match table_key(table) {
+        Some(key) => key,
+        None => return format!("{} ON CONFLICT DO NOTHING", insert),
+    };
+    let updates: Vec<String> = names
+        .iter()
+        .filter(|name| !key.contains(name))
+        .filter_map(|&name| {
+            let update = match (name, conflict) {
+                ("is_bot", _) => format!("{table}.is_bot OR excluded.is_bot", table = table),
+                (_, Conflict::Ignore) => return None,
+                (_, Conflict::KeepNewest(column)) => format!(
+                    "CASE WHEN {table}.{column} < excluded.{column} THEN excluded.{name} ELSE {table}.{name} END",
+                    table = table,
+                    column = column,
+                    name = name
+                ),
+                (_, Conflict::KeepOldest(column)) => format!(
+                    "CASE WHEN {table}.{column} > excluded.{column} THEN excluded.{name} ELSE {table}.{name} END",
+                    table = table,
+                    column = column,
+                    name = name
+                ),
+                (_, Conflict::Replace) => format!("excluded.{}", name),
+            };
+            Some(format!("{} = {}", name, update))
+        })
+        .collect();
+    let on_conflict = format!("ON CONFLICT ({}) DO", key_expressions(&key).join(", "));
+    if updates.is_empty() {
+        format!("{} {} NOTHING", insert, on_conflict)
+    } else {
+        format!("{} {} UPDATE SET {}", insert, on_conflict, updates.join(", "))
+    }
+}
+
+/// Times are stored as text that sorts in time order, so the keep newest and oldest checks work.
+fn sqlite_value(value: &Value) -> SqliteValue {
+    match *value {
+        Value::Null => SqliteValue::Null,
+        Value::Int64(number) => SqliteValue::Integer(number),
+        Value::Boolean(flag) => SqliteValue::Integer(flag as i64),
+        Value::Utf8(ref text) => SqliteValue::Text(text.clone()),
+        Value::Timestamp(time) => SqliteValue::Text(time.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+        Value::Date(date) => SqliteValue::Text(date.format("%Y-%m-%d").to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use chrono::{TimeZone, Utc};
+    use records::Record;
+    use sqlite::rusqlite::Connection;
+    use sqlite::SqliteSink;
+    use types::{CommitEvent, RepoIdToName};
+
+    #[test]
+    fn repo_mapping_keeps_the_newest_name() {
+        let path = env::temp_dir().join("rvh-sqlite-test.sqlite");
+        let _ = fs::remove_file(&path);
+        let mut sink = SqliteSink::open(&path).unwrap();
+
+        let mappings = vec![
+            RepoIdToName {
+                repo_id: 1,
+                repo_name: "foo/baz".to_string(),
+                event_timestamp: Utc.ymd(2017, 2, 1).and_hms(0, 0, 0),
+            },
+            RepoIdToName {
+                repo_id: 1,
+                repo_name: "foo/bar".to_string(),
+                event_timestamp: Utc.ymd(2017, 1, 1).and_hms(0, 0, 0),
+            },
+        ];
+        let rows: Vec<_> = mappings.iter().map(|mapping| mapping.values()).collect();
+        sink.insert("repo_mapping", &RepoIdToName::columns(), &rows).unwrap();
+
+        let committer = CommitEvent {
+            actor: "foo".to_string(),
+            repo_id: 1,
+            period: None,
+            is_bot: false,
+        };
+        let rows = vec![committer.values(), committer.values()];
+        sink.insert("committer_repo_id_names", &CommitEvent::columns(), &rows).unwrap();
+        sink.insert("committer_repo_id_names", &CommitEvent::columns(), &rows).unwrap();
+        let bot = CommitEvent {
+            is_bot: true,
+            ..committer.clone()
+        };
+        sink.insert("committer_repo_id_names", &CommitEvent::columns(), &[bot.values()]).unwrap();
+        sink.insert("committer_repo_id_names", &CommitEvent::columns(), &rows).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let name: String = connection
+            .query_row("SELECT repo_name FROM repo_mapping WHERE repo_id = 1", &[] as &[&str], |row| row.get(0))
+            .unwrap();
+        assert_eq!("foo/baz", name);
+        let (committers, bots): (i64, i64) = connection
+            .query_row(
+                "SELECT count(*), sum(is_bot) FROM committer_repo_id_names",
+                &[] as &[&str],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((1, 1), (committers, bots));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use chrono::NaiveDate;
+use types::*;
+
+/// How many accounts starred a repo on a day.  Starring, unstarring and starring again on the same day is one star.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct DailyStars {
+    pub repo_id: i64,
+    pub starred_on: NaiveDate,
+    pub stars: usize,
+}
+
+/// How many different accounts starred a repo over the whole run.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct StarStats {
+    pub repo_id: i64,
+    pub stargazers: usize,
+}
+
+/// Collects stars over a whole run.  Stargazers are kept as 64 bit hashes of their names instead of the names,
+/// which could count two stargazers of a repo as one but is very unlikely to.
+#[derive(Debug, Clone, Default)]
+pub struct StarCounter {
+    stars: HashSet<(i64, NaiveDate, u64)>,
+}
+
+impl StarCounter {
+    pub fn new() -> StarCounter {
+        StarCounter {
+            stars: HashSet::new(),
+        }
+    }
+
+    pub fn add(&mut self, star: &StarEvent) {
+        let mut hasher = DefaultHasher::new();
+        star.actor.hash(&mut hasher);
+        self.stars.insert((star.repo_id, star.starred_on, hasher.finish()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.stars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stars.is_empty()
+    }
+
+    pub fn daily_stars(&self) -> Vec<DailyStars> {
+        let mut days: HashMap<(i64, NaiveDate), usize> = HashMap::new();
+        for &(repo_id, starred_on, _) in &self.stars {
+            *days.entry((repo_id, starred_on)).or_insert(0) += 1;
+        }
+        let mut daily: Vec<DailyStars> = days
+            .into_iter()
+            .map(|((repo_id, starred_on), stars)| DailyStars {
+                repo_id: repo_id,
+                starred_on: starred_on,
+                stars: stars,
+            })
+            .collect();
+        daily.sort();
+        daily
+    }
+
+    pub fn star_stats(&self) -> Vec<StarStats> {
+        let stargazers: HashSet<(i64, u64)> = self.stars
+            .iter()
+            .map(|&(repo_id, _, actor)| (repo_id, actor))
+            .collect();
+        let mut repos: HashMap<i64, usize> = HashMap::new();
+        for (repo_id, _) in stargazers {
+            *repos.entry(repo_id).or_insert(0) += 1;
+        }
+        let mut stats: Vec<StarStats> = repos
+            .into_iter()
+            .map(|(repo_id, stargazers)| StarStats {
+                repo_id: repo_id,
+                stargazers: stargazers,
+            })
+            .collect();
+        stats.sort();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use stars::*;
+
+    #[test]
+    fn daily_stars_and_stargazers() {
+        let mut counter = StarCounter::new();
+        for &(repo_id, day, actor) in &[
+            (1, 1, "alice"),
+            (1, 1, "bob"),
+            // starred again after unstarring: still one star that day
+            (1, 1, "alice"),
+            (1, 2, "alice"),
+            (2, 2, "bob"),
+        ] {
+            counter.add(&StarEvent {
+                repo_id: repo_id,
+                starred_on: NaiveDate::from_ymd(2016, 1, day),
+                actor: actor.to_string(),
+            });
+        }
+
+        assert_eq!(
+            vec![
+                DailyStars {
+                    repo_id: 1,
+                    starred_on: NaiveDate::from_ymd(2016, 1, 1),
+                    stars: 2,
+                },
+                DailyStars {
+                    repo_id: 1,
+                    starred_on: NaiveDate::from_ymd(2016, 1, 2),
+                    stars: 1,
+                },
+                DailyStars {
+                    repo_id: 2,
+                    starred_on: NaiveDate::from_ymd(2016, 1, 2),
+                    stars: 1,
+                },
+            ],
+            counter.daily_stars()
+        );
+        assert_eq!(
+            vec![
+                StarStats {
+                    repo_id: 1,
+                    stargazers: 2,
+                },
+                StarStats {
+                    repo_id: 2,
+                    stargazers: 1,
+                },
+            ],
+            counter.star_stats()
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Which repos to slice out of the archive.  Repos are given by ID or `owner/name`, names ignoring case.
+/// A repo is picked if it's on the allow list and it isn't on the deny list.
+///
+/// Names only match events made while the repo had that name.  Adding the repo mapping output with
+/// `add_repo_mapping` adds the IDs of every repo that's had an allowed or denied name, so events from
+/// before or after a rename are matched too.
+#[derive(Debug, Clone, Default)]
+pub struct RepoSelector {
+    pub allow_ids: HashSet<i64>,
+    pub allow_names: HashSet<String>,
+    pub deny_ids: HashSet<i64>,
+    pub deny_names: HashSet<String>,
+}
+
+impl RepoSelector {
+    /// Build a selector from SUBSET_ALLOW and SUBSET_DENY.  Each is a comma or newline separated list
+    /// of repo IDs and names, or the path of a file with one in.
+    pub fn from_env() -> RepoSelector {
+        let (allow_ids, allow_names) = repos_from_env("SUBSET_ALLOW");
+        let (deny_ids, deny_names) = repos_from_env("SUBSET_DENY");
+        RepoSelector {
+            allow_ids: allow_ids,
+            allow_names: allow_names,
+            deny_ids: deny_ids,
+            deny_names: deny_names,
+        }
+    }
+
+    /// Add the repo's ID if it had an allowed or denied name, from a row of repo mapping output.
+    pub fn add_repo_mapping(&mut self, repo_id: i64, repo_name: &str) {
+        let repo_name = repo_name.to_lowercase();
+        if self.allow_names.contains(&repo_name) {
+            self.allow_ids.insert(repo_id);
+        }
+        if self.deny_names.contains(&repo_name) {
+            self.deny_ids.insert(repo_id);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow_ids.is_empty() && self.allow_names.is_empty()
+    }
+
+    pub fn is_selected(&self, repo_id: i64, repo_name: &str) -> bool {
+        let repo_name = repo_name.to_lowercase();
+        if self.deny_ids.contains(&repo_id) || self.deny_names.contains(&repo_name) {
+            return false;
+        }
+        self.allow_ids.contains(&repo_id) || self.allow_names.contains(&repo_name)
+    }
+}
+
+/// Split a list of repos into IDs and lower cased names.
+pub fn parse_repo_list(list: &str) -> (HashSet<i64>, HashSet<String>) {
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    for repo in list.split(|c| c == ',' || c == '\n').map(|repo| repo.trim()) {
+        if repo == "" {
+            continue;
+        }
+        match repo.parse::<i64>() {
+            Ok(id) => {
+                ids.insert(id);
+            }
+            Err(_) => {
+                names.insert(repo.to_lowercase());
+            }
+        }
+    }
+    (ids, names)
+}
+
+fn repos_from_env(name: &str) -> (HashSet<i64>, HashSet<String>) {
+    let list = match env::var(name) {
+        Ok(list) => list,
+        Err(_) => return (HashSet::new(), HashSet::new()),
+    };
+    if !Path::new(&list).is_file() {
+        return parse_repo_list(&list);
+    }
+    let mut contents = String::new();
+    File::open(&list)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .expect("Couldn't read the list of repos");
+    parse_repo_list(&contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use subset::{parse_repo_list, RepoSelector};
+
+    #[test]
+    fn renamed_repos_are_selected() {
+        let (allow_ids, allow_names) = parse_repo_list("1234, Foo/Bar\nrust-lang/rust");
+        let (deny_ids, deny_names) = parse_repo_list("9");
+        let mut selector = RepoSelector {
+            allow_ids: allow_ids,
+            allow_names: allow_names,
+            deny_ids: deny_ids,
+            deny_names: deny_names,
+        };
+        assert!(selector.is_selected(1234, "someone/something"));
+        assert!(selector.is_selected(5, "foo/bar"));
+        assert!(!selector.is_selected(5, "foo/old-bar"));
+
+        selector.add_repo_mapping(5, "Foo/Bar");
+        selector.add_repo_mapping(9, "rust-lang/rust");
+        assert!(selector.is_selected(5, "foo/old-bar"));
+        // denied by ID even though the name is allowed:
+        assert!(!selector.is_selected(9, "rust-lang/rust"));
+        assert!(!selector.is_selected(6, "foo/baz"));
+        assert!(!RepoSelector::default().is_selected(6, "foo/baz"));
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt::Display;
 use std::str::FromStr;
 use serde::de::{self, Deserialize, Deserializer};
 use serde_json::Value;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
 
 // source events from github archive
 
@@ -17,6 +17,13 @@ pub struct Actor {
     pub login: Option<String>,
 }
 
+/// GitHub organization that owns the repo of an event.  Only in newer events.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Org {
+    #[serde(default = "id_not_specified")] pub id: i64,
+    pub login: Option<String>,
+}
+
 /// GitHub repository.  Assuming the ID stays constant but the name can change.
 #[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Repo {
@@ -24,25 +31,117 @@ pub struct Repo {
     pub name: String,
 }
 
-/// Pull request in an event.  We only care if it was merged or not.
+/// Pull request in an event.
 #[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct PullRequest {
+    pub number: Option<i64>,
     pub merged: Option<bool>,
     #[serde(rename = "user")] pub actor: Option<Actor>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub base: Option<Branch>,
+    pub head: Option<Branch>,
+    pub additions: Option<i64>,
+    pub deletions: Option<i64>,
+}
+
+/// Base or head of a pull request.  The repo is missing if it was deleted.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Branch {
+    #[serde(rename = "ref")] pub branch: Option<String>,
+    pub repo: Option<Repo>,
+}
+
+/// Author of a git commit, as written in the commit.  Not necessarily a GitHub account.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct CommitAuthor {
+    pub name: Option<String>,
+    pub email: Option<String>,
 }
 
 /// A git commit.
+/// `distinct` is false if the commit was already pushed to the repo, EG on another branch.
 #[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Commit {
     pub sha: Option<String>,
+    pub author: Option<CommitAuthor>,
+    pub distinct: Option<bool>,
 }
 
-/// Type containing if it's a push event or pull request event.
+/// An issue, or the issue side of a pull request if `pull_request` is there.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Issue {
+    pub number: Option<i64>,
+    pub user: Option<Actor>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub pull_request: Option<IssuePullRequest>,
+}
+
+/// Only there when an issue is really a pull request.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct IssuePullRequest {
+    pub url: Option<String>,
+}
+
+/// Comment on an issue or pull request.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct IssueComment {
+    pub user: Option<Actor>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Label added to or removed from an issue.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Label {
+    pub name: Option<String>,
+}
+
+/// A review of a pull request.  State is approved, changes_requested, commented or dismissed.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Review {
+    pub user: Option<Actor>,
+    pub state: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+/// A GitHub release and the tag it's for.
 #[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Release {
+    pub tag_name: Option<String>,
+    pub prerelease: Option<bool>,
+    pub draft: Option<bool>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// The new repository made by forking.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Forkee {
+    #[serde(default = "id_not_specified")] pub id: i64,
+    pub full_name: Option<String>,
+    pub owner: Option<Actor>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Type containing if it's a push event or pull request event.
+/// `size` is the number of commits in a push and `distinct_size` how many of those are new to the repo.
+/// Create and delete events say what was made or removed with `ref` and `ref_type` (tag, branch or repository).
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Payload {
     pub action: Option<String>,
     #[serde(rename = "pull_request")] pub pull_request: Option<PullRequest>,
     pub commits: Option<Vec<Commit>>,
+    pub size: Option<i64>,
+    pub distinct_size: Option<i64>,
+    pub forkee: Option<Forkee>,
+    pub issue: Option<Issue>,
+    pub comment: Option<IssueComment>,
+    pub label: Option<Label>,
+    #[serde(rename = "ref")] pub ref_name: Option<String>,
+    pub ref_type: Option<String>,
+    pub release: Option<Release>,
+    pub review: Option<Review>,
 }
 
 /// 2015 and later github archive event.
@@ -53,6 +152,7 @@ pub struct Event {
     #[serde(rename = "type")] pub event_type: String,
     pub actor: Actor,
     pub repo: Repo,
+    pub org: Option<Org>,
     pub payload: Option<Payload>,
 }
 
@@ -70,6 +170,7 @@ impl Event {
                 id: -1,
                 name: "n/a".to_string(),
             },
+            org: None,
             payload: None,
             created_at: Utc.ymd(2010, 1, 1).and_hms(0, 0, 0),
         }
@@ -100,6 +201,8 @@ impl Event {
                     None => "".to_string(),
                 },
                 repo_id: self.repo.id,
+                period: None,
+                is_bot: false,
             }
         } else {
             CommitEvent {
@@ -108,8 +211,470 @@ impl Event {
                     None => "".to_string(),
                 },
                 repo_id: self.repo.id,
+                period: None,
+                is_bot: false,
+            }
+        }
+    }
+
+    /// The commits in a push event, credited to the commit author instead of the pusher.
+    /// Commits that aren't distinct were already in the repo and aren't counted again.
+    /// The author is their email, or their name if the email is missing.
+    pub fn as_authored_commits(&self) -> Vec<AuthoredCommit> {
+        self.authored_commits(false)
+    }
+
+    fn authored_commits(&self, lowercase_emails: bool) -> Vec<AuthoredCommit> {
+        if !self.is_direct_push_event() {
+            return Vec::new();
+        }
+        let payload = match self.payload {
+            Some(ref payload) => payload,
+            None => return Vec::new(),
+        };
+        // Nothing new in this push, such as a branch made from commits the repo already has.
+        if payload.distinct_size == Some(0) {
+            return Vec::new();
+        }
+        match payload.commits {
+            Some(ref commits) => commits
+                .iter()
+                .filter(|commit| commit.distinct != Some(false))
+                .filter_map(|commit| {
+                    let sha = match commit.sha {
+                        Some(ref sha) => sha.clone(),
+                        None => return None,
+                    };
+                    let author = match commit.author {
+                        Some(ref author) => match (&author.email, &author.name) {
+                            (&Some(ref email), _) if email != "" && lowercase_emails => email.to_lowercase(),
+                            (&Some(ref email), _) if email != "" => email.clone(),
+                            (_, &Some(ref name)) => name.clone(),
+                            _ => "".to_string(),
+                        },
+                        None => "".to_string(),
+                    };
+                    Some(AuthoredCommit {
+                        repo_id: self.repo.id,
+                        sha: sha,
+                        author: author,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// How many of a push's new commits aren't in its commit list.  The archive only lists the first 20 commits
+    /// of a push, so for bigger pushes `distinct_size` is the only count of the rest.  Their authors aren't known.
+    pub fn unlisted_commits(&self) -> i64 {
+        if !self.is_direct_push_event() {
+            return 0;
+        }
+        let payload = match self.payload {
+            Some(ref payload) => payload,
+            None => return 0,
+        };
+        let commits = match payload.commits {
+            Some(ref commits) => commits,
+            None => return 0,
+        };
+        match (payload.size, payload.distinct_size) {
+            (Some(size), Some(distinct_size)) if size > commits.len() as i64 => {
+                let listed = commits
+                    .iter()
+                    .filter(|commit| commit.distinct != Some(false))
+                    .count() as i64;
+                if distinct_size > listed {
+                    distinct_size - listed
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// When the committers of the event contributed: when a pull request was opened, the event time otherwise.
+    pub fn contributed_at(&self) -> DateTime<Utc> {
+        if self.event_type == "PullRequestEvent" {
+            if let Some(ref payload) = self.payload {
+                if let Some(ref pull_request) = payload.pull_request {
+                    if let Some(created_at) = pull_request.created_at {
+                        return created_at;
+                    }
+                }
             }
         }
+        self.created_at
+    }
+
+    /// Like `as_commit_event` but push events are credited to the authors of the pushed commits
+    /// instead of the account that pushed them, when `attribute_to_authors` is set.
+    pub fn as_commit_events(&self, attribute_to_authors: bool) -> Vec<CommitEvent> {
+        if !attribute_to_authors || self.event_type != "PushEvent" {
+            return vec![self.as_commit_event()];
+        }
+        // The same person can commit as Foo@Example.com and foo@example.com.
+        let mut authors: Vec<CommitEvent> = self.authored_commits(true)
+            .into_iter()
+            .filter(|commit| commit.author != "")
+            .map(|commit| CommitEvent {
+                actor: commit.author,
+                repo_id: commit.repo_id,
+                period: None,
+                is_bot: false,
+            })
+            .collect();
+        authors.sort();
+        authors.dedup();
+        authors
+    }
+
+    /// Despite the name, WatchEvents are when someone stars a repo.
+    pub fn is_star_event(&self) -> bool {
+        self.event_type == "WatchEvent"
+    }
+
+    pub fn as_star_event(&self) -> StarEvent {
+        StarEvent {
+            repo_id: self.repo.id,
+            starred_on: self.created_at.naive_utc().date(),
+            actor: match self.actor.login {
+                Some(ref actor_login) => actor_login.clone(),
+                None => "".to_string(),
+            },
+        }
+    }
+
+    pub fn is_fork_event(&self) -> bool {
+        self.event_type == "ForkEvent"
+    }
+
+    /// The event's repo is the parent, the forkee is the new fork.
+    /// None if the payload doesn't say what the fork is.
+    pub fn as_fork_event(&self) -> Option<ForkEvent> {
+        if !self.is_fork_event() {
+            return None;
+        }
+        let forkee = match self.payload {
+            Some(ref payload) => match payload.forkee {
+                Some(ref forkee) if forkee.id != -1 => forkee,
+                _ => return None,
+            },
+            None => return None,
+        };
+        let fork_owner = match forkee.owner {
+            Some(ref owner) => owner.login.clone(),
+            None => None,
+        };
+        Some(ForkEvent {
+            parent_repo_id: self.repo.id,
+            fork_repo_id: forkee.id,
+            fork_owner: match fork_owner.or(self.actor.login.clone()) {
+                Some(login) => login,
+                None => "".to_string(),
+            },
+            created_at: match forkee.created_at {
+                Some(created_at) => created_at,
+                None => self.created_at,
+            },
+        })
+    }
+
+    /// State of a pull request as of this event.  None if it isn't a pull request event.
+    pub fn as_pr_lifecycle(&self) -> Option<PrLifecycle> {
+        if self.event_type != "PullRequestEvent" {
+            return None;
+        }
+        let pr = match self.payload {
+            Some(ref payload) => match payload.pull_request {
+                Some(ref pr) => pr,
+                None => return None,
+            },
+            None => return None,
+        };
+        let number = match pr.number {
+            Some(number) => number,
+            None => return None,
+        };
+        let base_repo_id = pr.base.as_ref().and_then(|base| base.repo.as_ref()).map(|repo| repo.id);
+        let head_repo_id = pr.head.as_ref().and_then(|head| head.repo.as_ref()).map(|repo| repo.id);
+        Some(PrLifecycle {
+            repo_id: self.repo.id,
+            number: number,
+            author: match pr.actor {
+                Some(ref actor) => match actor.login {
+                    Some(ref login) => login.clone(),
+                    None => "".to_string(),
+                },
+                None => "".to_string(),
+            },
+            opened_at: pr.created_at,
+            closed_at: pr.closed_at,
+            merged_at: pr.merged_at,
+            additions: pr.additions,
+            deletions: pr.deletions,
+            base_branch: match pr.base {
+                Some(ref base) => match base.branch {
+                    Some(ref branch) => branch.clone(),
+                    None => "".to_string(),
+                },
+                None => "".to_string(),
+            },
+            // A deleted head repo was a fork, it couldn't be deleted without deleting the base too.
+            cross_repo: head_repo_id != base_repo_id,
+            updated_at: self.created_at,
+        })
+    }
+
+    pub fn is_issue_event(&self) -> bool {
+        self.event_type == "IssuesEvent" || self.event_type == "IssueCommentEvent"
+    }
+
+    /// Something happening to an issue: opened, closed, reopened, labeled, commented on, etc.
+    /// None if it isn't an issue event, or it's a comment being edited or deleted.
+    pub fn as_issue_activity(&self) -> Option<IssueActivity> {
+        if !self.is_issue_event() {
+            return None;
+        }
+        let payload = match self.payload {
+            Some(ref payload) => payload,
+            None => return None,
+        };
+        let issue = match payload.issue {
+            Some(ref issue) => issue,
+            None => return None,
+        };
+        let number = match issue.number {
+            Some(number) => number,
+            None => return None,
+        };
+        let event_actor = match self.actor.login {
+            Some(ref login) => login.clone(),
+            None => "".to_string(),
+        };
+        let (action, actor, happened_at) = if self.event_type == "IssueCommentEvent" {
+            if payload.action != Some("created".to_string()) {
+                return None;
+            }
+            match payload.comment {
+                Some(ref comment) => (
+                    "commented".to_string(),
+                    match comment.user {
+                        Some(Actor {
+                            login: Some(ref login),
+                            ..
+                        }) => login.clone(),
+                        _ => event_actor,
+                    },
+                    comment.created_at.unwrap_or(self.created_at),
+                ),
+                None => ("commented".to_string(), event_actor, self.created_at),
+            }
+        } else {
+            match payload.action {
+                Some(ref action) => (action.clone(), event_actor, self.created_at),
+                None => return None,
+            }
+        };
+        Some(IssueActivity {
+            event_id: self.id,
+            repo_id: self.repo.id,
+            number: number,
+            action: action,
+            actor: actor,
+            label: match payload.label {
+                Some(Label {
+                    name: Some(ref name),
+                }) => name.clone(),
+                _ => "".to_string(),
+            },
+            happened_at: happened_at,
+            issue_author: match issue.user {
+                Some(Actor {
+                    login: Some(ref login),
+                    ..
+                }) => login.clone(),
+                _ => "".to_string(),
+            },
+            opened_at: issue.created_at,
+            is_pull_request: issue.pull_request.is_some(),
+        })
+    }
+
+    /// The organization the repo belongs to.  If the event doesn't say, it's the owner
+    /// part of the repo's `owner/name`, which may be a user instead of an organization.
+    pub fn org_login(&self) -> String {
+        match self.org {
+            Some(Org {
+                login: Some(ref login),
+                ..
+            }) => login.clone(),
+            _ => match self.repo.name.find('/') {
+                Some(slash) => self.repo.name[..slash].to_string(),
+                None => "".to_string(),
+            },
+        }
+    }
+
+    /// What the event says about its org: the repo, the type of event and who committed if it's a commit.
+    pub fn as_org_activity(&self, attribute_to_authors: bool) -> OrgActivity {
+        OrgActivity {
+            org: self.org_login(),
+            repo_id: self.repo.id,
+            event_type: self.event_type.clone(),
+            committers: match self.is_commit_event() {
+                true => self.as_commit_events(attribute_to_authors)
+                    .into_iter()
+                    .map(|committer| (committer.actor, committer.is_bot))
+                    .collect(),
+                false => Vec::new(),
+            },
+        }
+    }
+
+    /// Tags and branches being created or deleted, and releases.  Creating the repository itself isn't one.
+    pub fn is_release_event(&self) -> bool {
+        match self.event_type.as_ref() {
+            "ReleaseEvent" => true,
+            "CreateEvent" | "DeleteEvent" => match self.payload {
+                Some(ref payload) => match payload.ref_type {
+                    Some(ref ref_type) => ref_type == "tag" || ref_type == "branch",
+                    None => false,
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// A tag or branch created or deleted, or a release published.  None if it isn't one or the name is missing.
+    pub fn as_release_activity(&self) -> Option<ReleaseActivity> {
+        if !self.is_release_event() {
+            return None;
+        }
+        let payload = match self.payload {
+            Some(ref payload) => payload,
+            None => return None,
+        };
+        let (kind, action, name, prerelease, happened_at) = if self.event_type == "ReleaseEvent" {
+            let release = match payload.release {
+                Some(ref release) => release,
+                None => return None,
+            };
+            if release.draft == Some(true) {
+                return None;
+            }
+            (
+                "release".to_string(),
+                match payload.action {
+                    Some(ref action) => action.clone(),
+                    None => "published".to_string(),
+                },
+                release.tag_name.clone(),
+                release.prerelease.unwrap_or(false),
+                release.published_at.unwrap_or(self.created_at),
+            )
+        } else {
+            (
+                payload.ref_type.clone().unwrap_or_default(),
+                match self.event_type.as_ref() {
+                    "CreateEvent" => "created".to_string(),
+                    _ => "deleted".to_string(),
+                },
+                payload.ref_name.clone(),
+                false,
+                self.created_at,
+            )
+        };
+        let name = match name {
+            Some(name) => name,
+            None => return None,
+        };
+        Some(ReleaseActivity {
+            event_id: self.id,
+            repo_id: self.repo.id,
+            kind: kind,
+            action: action,
+            name: name,
+            prerelease: prerelease,
+            actor: match self.actor.login {
+                Some(ref login) => login.clone(),
+                None => "".to_string(),
+            },
+            happened_at: happened_at,
+        })
+    }
+
+    pub fn is_review_event(&self) -> bool {
+        self.event_type == "PullRequestReviewEvent" || self.event_type == "PullRequestReviewCommentEvent"
+    }
+
+    /// Someone reviewing a pull request, or commenting on its diff (a `commented` review).
+    /// None if it isn't a review event or doesn't say which pull request it's for.
+    pub fn as_pr_review(&self) -> Option<PrReview> {
+        if !self.is_review_event() {
+            return None;
+        }
+        let payload = match self.payload {
+            Some(ref payload) => payload,
+            None => return None,
+        };
+        let number = match payload.pull_request {
+            Some(PullRequest {
+                number: Some(number),
+                ..
+            }) => number,
+            _ => return None,
+        };
+        let event_actor = match self.actor.login {
+            Some(ref login) => login.clone(),
+            None => "".to_string(),
+        };
+        let (reviewer, state, submitted_at) = if self.event_type == "PullRequestReviewEvent" {
+            match payload.review {
+                Some(ref review) => (
+                    match review.user {
+                        Some(Actor {
+                            login: Some(ref login),
+                            ..
+                        }) => login.clone(),
+                        _ => event_actor,
+                    },
+                    match review.state {
+                        Some(ref state) => state.to_lowercase(),
+                        None => return None,
+                    },
+                    review.submitted_at.unwrap_or(self.created_at),
+                ),
+                None => return None,
+            }
+        } else {
+            match payload.comment {
+                Some(ref comment) => (
+                    match comment.user {
+                        Some(Actor {
+                            login: Some(ref login),
+                            ..
+                        }) => login.clone(),
+                        _ => event_actor,
+                    },
+                    "commented".to_string(),
+                    comment.created_at.unwrap_or(self.created_at),
+                ),
+                None => (event_actor, "commented".to_string(), self.created_at),
+            }
+        };
+        Some(PrReview {
+            event_id: self.id,
+            repo_id: self.repo.id,
+            number: number,
+            reviewer: reviewer,
+            state: state,
+            submitted_at: submitted_at,
+            is_bot: false,
+        })
     }
 
     // Also covers placeholder Events made in the constructor above
@@ -259,6 +824,526 @@ mod tests {
         assert_eq!("committer-login", commit_event.actor);
         assert_eq!(155, commit_event.repo_id);
     }
+
+    // A merged PR from a fork keeps when it was opened and merged, its size and the branch it went into
+    #[test]
+    fn merged_pull_request_lifecycle() {
+        use types::Event;
+        let pr_text = r#"{
+  "id": "12346",
+  "type": "PullRequestEvent",
+  "actor": {
+    "id": 1,
+    "login": "owner-login"
+    },
+  "repo": {
+    "id": 155,
+    "name": "foo/reponame"
+  },
+  "payload": {
+    "action": "closed",
+    "pull_request": {
+      "number": 42,
+      "user": {
+        "id": 5,
+        "login": "committer-login"
+      },
+      "created_at": "2017-04-30T13:14:51Z",
+      "closed_at": "2017-05-01T07:01:53Z",
+      "merged_at": "2017-05-01T07:01:53Z",
+      "head": {
+        "repo": {
+          "id": 156,
+          "name": "committer-login/reponame"
+        }
+      },
+      "base": {
+        "ref": "master",
+        "repo": {
+          "id": 155,
+          "name": "foo/reponame"
+        }
+      },
+      "merged": true,
+      "additions": 10,
+      "deletions": 2
+    }
+  },
+  "created_at": "2017-05-01T07:01:53Z"
+}"#;
+        let event: Event = match serde_json::from_str(&pr_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        let pr = event.as_pr_lifecycle().expect("Should be a pull request");
+
+        assert_eq!(155, pr.repo_id);
+        assert_eq!(42, pr.number);
+        assert_eq!("committer-login", pr.author);
+        assert_eq!("2017-04-30 13:14:51 UTC", pr.opened_at.unwrap().to_string());
+        assert_eq!("2017-05-01 07:01:53 UTC", pr.merged_at.unwrap().to_string());
+        assert_eq!(Some(10), pr.additions);
+        assert_eq!(Some(2), pr.deletions);
+        assert_eq!("master", pr.base_branch);
+        assert!(pr.cross_repo);
+        assert_eq!("2017-04-30 13:14:51 UTC", event.contributed_at().to_string());
+    }
+
+    // Commits are credited to their authors and ones already in the repo are skipped
+    #[test]
+    fn pushed_commits_credited_to_authors() {
+        use types::Event;
+        let push_text = r#"{
+  "id": "5785865383",
+  "type": "PushEvent",
+  "actor": {
+    "id": 1,
+    "login": "maintainer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "push_id": 1234568,
+    "size": 3,
+    "distinct_size": 2,
+    "commits": [
+      {
+        "sha": "0a7c6e9d1f8d2b5d9e6a3c1b7f4e2d8c9b0a1f2e",
+        "author": {
+          "email": "First@Example.com",
+          "name": "First Author"
+        },
+        "distinct": true
+      },
+      {
+        "sha": "1b8d7f0e2a9e3c6e0f7b4d2c8a5f3e9d0c1b2a3f",
+        "author": {
+          "name": "No Email"
+        },
+        "distinct": true
+      },
+      {
+        "sha": "2c9e8a1f3b0f4d7f1a8c5e3d9b6a4f0e1d2c3b4a",
+        "author": {
+          "email": "old@example.com",
+          "name": "Already Pushed"
+        },
+        "distinct": false
+      }
+    ]
+  },
+  "created_at": "2017-05-01T07:00:00Z"
+}"#;
+        let event: Event = match serde_json::from_str(&push_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        let commits = event.as_authored_commits();
+
+        assert_eq!(2, commits.len());
+        assert_eq!("First@Example.com", commits[0].author);
+        assert_eq!("0a7c6e9d1f8d2b5d9e6a3c1b7f4e2d8c9b0a1f2e", commits[0].sha);
+        assert_eq!("No Email", commits[1].author);
+        assert_eq!(255, commits[1].repo_id);
+
+        let committers = event.as_commit_events(true);
+        assert_eq!(2, committers.len());
+        assert_eq!("No Email", committers[0].actor);
+        assert_eq!("first@example.com", committers[1].actor);
+
+        let pushers = event.as_commit_events(false);
+        assert_eq!(1, pushers.len());
+        assert_eq!("maintainer", pushers[0].actor);
+    }
+
+    // The archive cuts a push's commit list off, distinct_size still counts the rest
+    #[test]
+    fn big_push_counts_unlisted_commits() {
+        use types::Event;
+        let push_text = r#"{
+  "id": "5785865390",
+  "type": "PushEvent",
+  "actor": {
+    "id": 1,
+    "login": "maintainer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "push_id": 1234570,
+    "size": 25,
+    "distinct_size": 23,
+    "commits": [
+      {
+        "sha": "0a7c6e9d1f8d2b5d9e6a3c1b7f4e2d8c9b0a1f2e",
+        "author": {
+          "email": "first@example.com"
+        },
+        "distinct": true
+      },
+      {
+        "sha": "2c9e8a1f3b0f4d7f1a8c5e3d9b6a4f0e1d2c3b4a",
+        "author": {
+          "email": "old@example.com"
+        },
+        "distinct": false
+      }
+    ]
+  },
+  "created_at": "2017-05-01T07:00:00Z"
+}"#;
+        let event: Event = match serde_json::from_str(&push_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        assert_eq!(1, event.as_authored_commits().len());
+        assert_eq!(22, event.unlisted_commits());
+    }
+
+    #[test]
+    fn watch_event_is_a_star() {
+        use types::Event;
+        use chrono::NaiveDate;
+        let watch_text = r#"{
+  "id": "5785865384",
+  "type": "WatchEvent",
+  "actor": {
+    "id": 7,
+    "login": "stargazer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "action": "started"
+  },
+  "created_at": "2017-05-01T23:59:59Z"
+}"#;
+        let event: Event = match serde_json::from_str(&watch_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        assert!(event.is_star_event());
+        let star = event.as_star_event();
+
+        assert_eq!("stargazer", star.actor);
+        assert_eq!(255, star.repo_id);
+        assert_eq!(NaiveDate::from_ymd(2017, 5, 1), star.starred_on);
+    }
+
+    #[test]
+    fn fork_event_edge() {
+        use types::Event;
+        let fork_text = r#"{
+  "id": "5785865385",
+  "type": "ForkEvent",
+  "actor": {
+    "id": 8,
+    "login": "forker"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "forkee": {
+      "id": 9001,
+      "name": "bar",
+      "full_name": "some-org/bar",
+      "owner": {
+        "id": 10,
+        "login": "some-org"
+      },
+      "created_at": "2017-05-01T07:00:00Z"
+    }
+  },
+  "created_at": "2017-05-01T07:00:01Z"
+}"#;
+        let event: Event = match serde_json::from_str(&fork_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        let fork = event.as_fork_event().expect("Should be a fork");
+
+        assert_eq!(255, fork.parent_repo_id);
+        assert_eq!(9001, fork.fork_repo_id);
+        assert_eq!("some-org", fork.fork_owner);
+        assert_eq!("2017-05-01 07:00:00 UTC", fork.created_at.to_string());
+    }
+
+    #[test]
+    fn issue_comment_activity() {
+        use types::Event;
+        let comment_text = r#"{
+  "id": "5785865386",
+  "type": "IssueCommentEvent",
+  "actor": {
+    "id": 11,
+    "login": "maintainer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "action": "created",
+    "issue": {
+      "number": 12,
+      "user": {
+        "id": 12,
+        "login": "reporter"
+      },
+      "labels": [],
+      "created_at": "2017-05-01T06:00:00Z",
+      "closed_at": null
+    },
+    "comment": {
+      "user": {
+        "id": 11,
+        "login": "maintainer"
+      },
+      "created_at": "2017-05-01T07:00:00Z"
+    }
+  },
+  "created_at": "2017-05-01T07:00:01Z"
+}"#;
+        let event: Event = match serde_json::from_str(&comment_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        let activity = event.as_issue_activity().expect("Should be issue activity");
+
+        assert_eq!(5785865386, activity.event_id);
+        assert_eq!(12, activity.number);
+        assert_eq!("commented", activity.action);
+        assert_eq!("maintainer", activity.actor);
+        assert_eq!("reporter", activity.issue_author);
+        assert_eq!("2017-05-01 07:00:00 UTC", activity.happened_at.to_string());
+        assert_eq!("2017-05-01 06:00:00 UTC", activity.opened_at.unwrap().to_string());
+        assert!(!activity.is_pull_request);
+    }
+
+    #[test]
+    fn time_buckets() {
+        use types::TimeBucket;
+        use chrono::{NaiveDate, TimeZone, Utc};
+        // a Thursday
+        let time = Utc.ymd(2016, 6, 16).and_hms(23, 59, 59);
+
+        assert_eq!(NaiveDate::from_ymd(2016, 6, 16), TimeBucket::Day.period_start(&time));
+        assert_eq!(NaiveDate::from_ymd(2016, 6, 13), TimeBucket::Week.period_start(&time));
+        assert_eq!(NaiveDate::from_ymd(2016, 6, 1), TimeBucket::Month.period_start(&time));
+        assert_eq!(NaiveDate::from_ymd(2016, 1, 1), TimeBucket::Year.period_start(&time));
+        assert_eq!(Ok(TimeBucket::Week), "week".parse::<TimeBucket>());
+    }
+
+    #[test]
+    fn org_from_event_or_repo_name() {
+        use types::Event;
+        let org_text = r#"{
+  "id": "5785865387",
+  "type": "IssuesEvent",
+  "actor": {
+    "id": 1,
+    "login": "someone"
+    },
+  "repo": {
+    "id": 255,
+    "name": "some-user/bar"
+  },
+  "org": {
+    "id": 77,
+    "login": "some-org"
+  },
+  "created_at": "2017-05-01T07:00:01Z"
+}"#;
+        let mut event: Event = match serde_json::from_str(&org_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        assert_eq!("some-org", event.org_login());
+        let activity = event.as_org_activity(false);
+        assert_eq!("IssuesEvent", activity.event_type);
+        assert!(activity.committers.is_empty());
+
+        event.org = None;
+        assert_eq!("some-user", event.org_login());
+    }
+
+    #[test]
+    fn tags_and_releases() {
+        use types::Event;
+        let tag_text = r#"{
+  "id": "5785865390",
+  "type": "CreateEvent",
+  "actor": {
+    "id": 1,
+    "login": "maintainer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "ref": "v1.0.0",
+    "ref_type": "tag",
+    "master_branch": "master"
+  },
+  "created_at": "2017-05-01T07:00:02Z"
+}"#;
+        let release_text = r#"{
+  "id": "5785865391",
+  "type": "ReleaseEvent",
+  "actor": {
+    "id": 1,
+    "login": "maintainer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "action": "published",
+    "release": {
+      "tag_name": "v1.1.0-rc1",
+      "prerelease": true,
+      "draft": false,
+      "published_at": "2017-05-01T07:00:03Z"
+    }
+  },
+  "created_at": "2017-05-01T07:00:04Z"
+}"#;
+        let repo_text = tag_text.replace("\"ref\": \"v1.0.0\"", "\"ref\": null").replace("\"tag\"", "\"repository\"");
+        let tag: Event = serde_json::from_str(&tag_text).expect("tag event should parse");
+        let release: Event = serde_json::from_str(&release_text).expect("release event should parse");
+        let repo: Event = serde_json::from_str(&repo_text).expect("repository event should parse");
+
+        let tag = tag.as_release_activity().expect("Should be a tag");
+        assert_eq!("tag", tag.kind);
+        assert_eq!("created", tag.action);
+        assert_eq!("v1.0.0", tag.name);
+        assert!(!tag.prerelease);
+
+        let release = release.as_release_activity().expect("Should be a release");
+        assert_eq!("release", release.kind);
+        assert_eq!("published", release.action);
+        assert_eq!("v1.1.0-rc1", release.name);
+        assert!(release.prerelease);
+        assert_eq!("2017-05-01 07:00:03 UTC", release.happened_at.to_string());
+
+        assert!(!repo.is_release_event());
+        assert_eq!(None, repo.as_release_activity());
+    }
+
+    #[test]
+    fn pull_request_reviews() {
+        use types::Event;
+        let review_text = r#"{
+  "id": "5785865392",
+  "type": "PullRequestReviewEvent",
+  "actor": {
+    "id": 2,
+    "login": "reviewer"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "action": "created",
+    "review": {
+      "user": {
+        "id": 2,
+        "login": "reviewer"
+      },
+      "state": "APPROVED",
+      "submitted_at": "2017-05-01T07:00:05Z"
+    },
+    "pull_request": {
+      "number": 42
+    }
+  },
+  "created_at": "2017-05-01T07:00:06Z"
+}"#;
+        let comment_text = r#"{
+  "id": "5785865393",
+  "type": "PullRequestReviewCommentEvent",
+  "actor": {
+    "id": 3,
+    "login": "commenter"
+    },
+  "repo": {
+    "id": 255,
+    "name": "foo/bar"
+  },
+  "payload": {
+    "action": "created",
+    "comment": {
+      "user": {
+        "id": 3,
+        "login": "commenter"
+      },
+      "created_at": "2017-05-01T07:00:07Z"
+    },
+    "pull_request": {
+      "number": 42
+    }
+  },
+  "created_at": "2017-05-01T07:00:08Z"
+}"#;
+        let review: Event = serde_json::from_str(&review_text).expect("review event should parse");
+        let comment: Event = serde_json::from_str(&comment_text).expect("review comment event should parse");
+
+        let review = review.as_pr_review().expect("Should be a review");
+        assert_eq!(42, review.number);
+        assert_eq!("reviewer", review.reviewer);
+        assert_eq!("approved", review.state);
+        assert_eq!("2017-05-01 07:00:05 UTC", review.submitted_at.to_string());
+
+        let comment = comment.as_pr_review().expect("Should be a review comment");
+        assert_eq!("commenter", comment.reviewer);
+        assert_eq!("commented", comment.state);
+    }
+
+    // Old events have the repo owner separate from the name and a local timestamp
+    #[test]
+    fn pre_2015_watch_event_converts() {
+        use types::Pre2015Event;
+        use chrono::NaiveDate;
+        let watch_text = r#"{
+  "repository": {
+    "id": 1234,
+    "name": "bar",
+    "owner": "foo"
+  },
+  "actor_attributes": {
+    "login": "old-stargazer"
+  },
+  "actor": "old-stargazer",
+  "created_at": "2013-01-01T20:00:24-08:00",
+  "payload": {
+    "action": "started"
+  },
+  "type": "WatchEvent"
+}"#;
+        let old_event: Pre2015Event = match serde_json::from_str(&watch_text) {
+            Ok(event) => event,
+            Err(err) => panic!("Found a weird line of json, got this error: {:?}.", err),
+        };
+        let event = old_event.as_event().expect("Should convert old event");
+
+        assert!(event.is_star_event());
+        assert_eq!("foo/bar", event.repo.name);
+        let star = event.as_star_event();
+        assert_eq!("old-stargazer", star.actor);
+        assert_eq!(1234, star.repo_id);
+        assert_eq!(NaiveDate::from_ymd(2013, 1, 2), star.starred_on);
+    }
 }
 
 /// Get the login for the user/actor
@@ -311,10 +1396,18 @@ impl<'de> Deserialize<'de> for Pre2015Actor {
     }
 }
 
+/// Repository in a pre-2015 event.  The name doesn't include the owner.
+#[derive(Deserialize, Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct OldRepository {
+    #[serde(default = "id_not_specified")] pub id: i64,
+    pub name: String,
+    pub owner: Option<String>,
+}
+
 /// A github archive event before 2015.
 #[derive(Deserialize, Debug, Clone)]
 pub struct Pre2015Event {
-    pub repository: Option<Repo>,
+    pub repository: Option<OldRepository>,
     pub repo: Option<Repo>,
     #[serde(rename = "type")] pub event_type: String,
     pub actor: Pre2015Actor,
@@ -331,6 +1424,8 @@ impl Pre2015Event {
         CommitEvent {
             actor: self.actor_name().to_string(),
             repo_id: self.repo_id(),
+            period: None,
+            is_bot: false,
         }
     }
 
@@ -338,6 +1433,48 @@ impl Pre2015Event {
         self.actor.actor.to_string()
     }
 
+    /// Convert to the 2015 and later layout so old events can go through the same pipeline.
+    /// Only the type, actor, repo and time are carried over: the old payloads are too different.
+    /// None if we can't tell which repo it's for or when it happened.
+    pub fn as_event(&self) -> Option<Event> {
+        let created_at = match DateTime::parse_from_rfc3339(&self.created_at) {
+            Ok(time) => time.with_timezone(&Utc),
+            Err(_) => return None,
+        };
+        if self.repo_id() == -1 {
+            return None;
+        }
+        Some(Event {
+            id: -1,
+            created_at: created_at,
+            event_type: self.event_type.clone(),
+            actor: Actor {
+                id: -1,
+                login: Some(self.actor_name()),
+            },
+            repo: Repo {
+                id: self.repo_id(),
+                name: self.repo_name(),
+            },
+            org: None,
+            payload: None,
+        })
+    }
+
+    /// Full `owner/name` of the repo.
+    pub fn repo_name(&self) -> String {
+        match self.repo {
+            Some(ref repo) => repo.name.clone(),
+            None => match self.repository {
+                Some(ref repository) => match repository.owner {
+                    Some(ref owner) => format!("{}/{}", owner, repository.name),
+                    None => repository.name.clone(),
+                },
+                None => "".to_string(),
+            },
+        }
+    }
+
     pub fn repo_id(&self) -> i64 {
         let repo_id = match self.repo {
             Some(ref repo) => repo.id,
@@ -390,10 +1527,178 @@ impl Pre2015Event {
 
 // -----------------------------------------------
 // events trimmed down to the fields we care about
+// period and is_bot are filled in by the pipeline, depending on the time bucket and how bots are handled.
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct CommitEvent {
     pub actor: String,
     pub repo_id: i64,
+    pub period: Option<NaiveDate>,
+    pub is_bot: bool,
+}
+
+/// Size of the time periods committers are counted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBucket {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl FromStr for TimeBucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TimeBucket, String> {
+        match s {
+            "day" => Ok(TimeBucket::Day),
+            "week" => Ok(TimeBucket::Week),
+            "month" => Ok(TimeBucket::Month),
+            "year" => Ok(TimeBucket::Year),
+            _ => Err(format!("Unknown time bucket {}, should be day, week, month or year", s)),
+        }
+    }
+}
+
+impl TimeBucket {
+    /// First day of the period the time is in.  Weeks start on Monday.
+    pub fn period_start(&self, time: &DateTime<Utc>) -> NaiveDate {
+        let date = time.naive_utc().date();
+        match *self {
+            TimeBucket::Day => date,
+            TimeBucket::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            TimeBucket::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            TimeBucket::Year => NaiveDate::from_ymd(date.year(), 1, 1),
+        }
+    }
+}
+
+/// One pull request and where it is in its life: opened, closed, merged.
+/// updated_at is when the event was made, the newest one has the most up to date state.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct PrLifecycle {
+    pub repo_id: i64,
+    pub number: i64,
+    pub author: String,
+    pub opened_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub additions: Option<i64>,
+    pub deletions: Option<i64>,
+    pub base_branch: String,
+    pub cross_repo: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A single thing happening to an issue.  Carries who opened the issue and when so first
+/// response times can be worked out from any one activity.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct IssueActivity {
+    pub event_id: i64,
+    pub repo_id: i64,
+    pub number: i64,
+    pub action: String,
+    pub actor: String,
+    pub label: String,
+    pub happened_at: DateTime<Utc>,
+    pub issue_author: String,
+    pub opened_at: Option<DateTime<Utc>>,
+    pub is_pull_request: bool,
+}
+
+/// An event along with the line of JSON it was parsed from, for passing events on untouched.
+#[derive(Debug, Clone)]
+pub struct RawEvent {
+    pub line: String,
+    pub event: Event,
+}
+
+/// A tag or branch being created or deleted, or a release.  `kind` is tag, branch or release and
+/// `name` is the tag or branch name.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct ReleaseActivity {
+    pub event_id: i64,
+    pub repo_id: i64,
+    pub kind: String,
+    pub action: String,
+    pub name: String,
+    pub prerelease: bool,
+    pub actor: String,
+    pub happened_at: DateTime<Utc>,
+}
+
+/// Someone reviewing a pull request.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct PrReview {
+    pub event_id: i64,
+    pub repo_id: i64,
+    pub number: i64,
+    pub reviewer: String,
+    pub state: String,
+    pub submitted_at: DateTime<Utc>,
+    pub is_bot: bool,
+}
+
+/// When someone first contributed to a repo, as far as we've seen.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct FirstContribution {
+    pub repo_id: i64,
+    pub actor: String,
+    pub first_contributed_at: DateTime<Utc>,
+    pub is_bot: bool,
+}
+
+/// An event boiled down to what org level rollups need.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct OrgActivity {
+    pub org: String,
+    pub repo_id: i64,
+    pub event_type: String,
+    /// Committer and if they're a bot.
+    pub committers: Vec<(String, bool)>,
+}
+
+/// How many events of a type happened in an org's repos.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct OrgEventCount {
+    pub org: String,
+    pub event_type: String,
+    pub events: usize,
+}
+
+/// An edge in the fork graph: fork_repo_id was forked from parent_repo_id.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct ForkEvent {
+    pub parent_repo_id: i64,
+    pub fork_repo_id: i64,
+    pub fork_owner: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Someone starring a repo on a day.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct StarEvent {
+    pub repo_id: i64,
+    pub starred_on: NaiveDate,
+    pub actor: String,
+}
+
+/// A single commit in a repo.  Sorts by repo and SHA so the same commit pushed again
+/// (force push, pushed to another branch) can be deduped.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct AuthoredCommit {
+    pub repo_id: i64,
+    pub sha: String,
+    pub author: String,
+}
+
+/// How many new commits an author pushed to a repo in a period.  Commits left out of big pushes' commit lists are
+/// counted with an empty author.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct CommitCount {
+    pub repo_id: i64,
+    pub author: String,
+    pub period: NaiveDate,
+    pub commits: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
@@ -0,0 +1,202 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// A file for the uploader to put in a bucket, in the parts it's uploaded in.  Files of one part are uploaded whole.
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub bucket: String,
+    pub key: String,
+    pub parts: Vec<Vec<u8>>,
+}
+
+/// Keeps what's written to it in parts of `part_size` bytes, the last one possibly shorter, so a file can be
+/// uploaded a part at a time without copying it.
+#[derive(Debug)]
+pub struct PartWriter {
+    part_size: usize,
+    parts: Vec<Vec<u8>>,
+}
+
+impl PartWriter {
+    pub fn new(part_size: usize) -> PartWriter {
+        PartWriter {
+            part_size: part_size,
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn into_parts(self) -> Vec<Vec<u8>> {
+        self.parts
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let full = match self.parts.last() {
+            Some(part) => part.len() == self.part_size,
+            None => true,
+        };
+        if full {
+            self.parts.push(Vec::new());
+        }
+        let part = self.parts.last_mut().expect("There's always a part to write to");
+        let written = buf.len().min(self.part_size - part.len());
+        part.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Split a file that's already in memory into parts.  It's only copied if it's more than one part.
+pub fn into_parts(body: Vec<u8>, part_size: usize) -> Vec<Vec<u8>> {
+    if body.len() <= part_size {
+        return vec![body];
+    }
+    let mut writer = PartWriter::new(part_size);
+    writer.write_all(&body).expect("Couldn't split into parts");
+    writer.into_parts()
+}
+
+/// Puts files on background threads so the thread making them can get back to work.  The queue is bounded:
+/// `send` blocks while it's full, so output can't pile up in memory faster than it's uploaded.
+///
+/// Once an upload fails, `send` and `finish` return its error, the rest of the queue is dropped and
+/// nothing more is uploaded.
+pub struct Uploader {
+    sender: Mutex<Option<SyncSender<Upload>>>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Uploader {
+    /// Start `threads` threads uploading with `put`, which does its own retrying.
+    pub fn new<F>(threads: usize, queue_size: usize, put: F) -> Uploader
+    where
+        F: Fn(Upload) -> Result<(), String> + Send + Sync + 'static,
+    {
+        let (sender, receiver) = sync_channel::<Upload>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let put = Arc::new(put);
+        let error: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                let receiver = receiver.clone();
+                let put = put.clone();
+                let error = error.clone();
+                thread::spawn(move || loop {
+                    let upload = match receiver.lock().expect("Couldn't lock the upload queue").recv() {
+                        Ok(upload) => upload,
+                        Err(_) => break,
+                    };
+                    // keep draining after a failure so senders don't block forever
+                    if error.lock().expect("Couldn't lock the upload error").is_some() {
+                        continue;
+                    }
+                    let key = upload.key.clone();
+                    if let Err(e) = put(upload) {
+                        println!("Upload of {} failed: {}", key, e);
+                        let mut error = error.lock().expect("Couldn't lock the upload error");
+                        if error.is_none() {
+                            *error = Some(e);
+                        }
+                    }
+                })
+            })
+            .collect();
+        Uploader {
+            sender: Mutex::new(Some(sender)),
+            threads: Mutex::new(handles),
+            error: error,
+        }
+    }
+
+    /// Queue a file, waiting for room in the queue if it's full.
+    pub fn send(&self, upload: Upload) -> Result<(), String> {
+        self.check()?;
+        match *self.sender.lock().expect("Couldn't lock the upload queue") {
+            Some(ref sender) => sender
+                .send(upload)
+                .map_err(|_| "The upload threads have stopped".to_string()),
+            None => Err("The uploader has already finished".to_string()),
+        }
+    }
+
+    /// Wait for everything queued to be uploaded.  The uploader can't be sent anything afterwards.
+    pub fn finish(&self) -> Result<(), String> {
+        self.sender.lock().expect("Couldn't lock the upload queue").take();
+        let handles: Vec<JoinHandle<()>> = self.threads
+            .lock()
+            .expect("Couldn't lock the upload threads")
+            .drain(..)
+            .collect();
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| "An upload thread panicked".to_string())?;
+        }
+        self.check()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match *self.error.lock().expect("Couldn't lock the upload error") {
+            Some(ref e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use uploader::{into_parts, Upload, Uploader};
+
+    fn upload(key: &str) -> Upload {
+        Upload {
+            bucket: "bucket".to_string(),
+            key: key.to_string(),
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn everything_is_uploaded() {
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let uploaded_by_put = uploaded.clone();
+        let uploader = Uploader::new(3, 1, move |upload: Upload| {
+            uploaded_by_put.lock().unwrap().push(upload.key.clone());
+            Ok(())
+        });
+        for index in 0..20 {
+            uploader.send(upload(&index.to_string())).unwrap();
+        }
+        uploader.finish().unwrap();
+
+        let mut uploaded = uploaded.lock().unwrap().clone();
+        uploaded.sort_by_key(|key| key.parse::<i32>().unwrap());
+        assert_eq!((0..20).map(|index| index.to_string()).collect::<Vec<String>>(), uploaded);
+        assert!(uploader.send(upload("late")).is_err());
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        let uploader = Uploader::new(2, 4, |upload: Upload| match upload.key.as_str() {
+            "bad" => Err("no luck".to_string()),
+            _ => Ok(()),
+        });
+        uploader.send(upload("good")).unwrap();
+        uploader.send(upload("bad")).unwrap();
+        assert_eq!(Err("no luck".to_string()), uploader.finish());
+    }
+
+    #[test]
+    fn parts() {
+        assert_eq!(vec![b"abc".to_vec()], into_parts(b"abc".to_vec(), 3));
+        assert_eq!(vec![b"abc".to_vec(), b"de".to_vec()], into_parts(b"abcde".to_vec(), 3));
+        assert_eq!(vec![Vec::<u8>::new()], into_parts(Vec::new(), 3));
+    }
+}
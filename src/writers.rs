@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use serde_json;
+use compression::read_output_file;
+use records::*;
+
+/// A row read back from CSV or JSON lines output: each column's value as it'd be in CSV.  Nulls are left out.
+pub type TextRow = HashMap<String, String>;
+
+/// CSV with a header row.  Fields are quoted when they have a comma, quote or line break in them, quotes
+/// doubled, and nulls are empty.  Timestamps are RFC 3339 in UTC and dates are `yyyy-mm-dd`.
+pub fn csv_rows(columns: &[Column], rows: &[Vec<Value>]) -> String {
+    let mut csv = Vec::new();
+    write_csv(&mut csv, columns, rows).expect("Couldn't write CSV to memory");
+    String::from_utf8(csv).expect("CSV should be UTF-8")
+}
+
+/// Write `csv_rows` a row at a time, so a compressor or file can take them without the whole file being made first.
+pub fn write_csv<W: Write>(out: &mut W, columns: &[Column], rows: &[Vec<Value>]) -> io::Result<()> {
+    let header = columns
+        .iter()
+        .map(|column| column.name)
+        .collect::<Vec<&str>>()
+        .join(",");
+    writeln!(out, "{}", header)?;
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(csv_field).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &Value) -> String {
+    match *value {
+        Value::Utf8(ref text) => {
+            if text.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+                format!("\"{}\"", text.replace("\"", "\"\""))
+            } else {
+                text.clone()
+            }
+        }
+        _ => plain_text(value),
+    }
+}
+
+/// A JSON object per row, with the keys in column order.
+pub fn json_lines(columns: &[Column], rows: &[Vec<Value>]) -> String {
+    let mut jsonl = Vec::new();
+    write_json_lines(&mut jsonl, columns, rows).expect("Couldn't write JSON lines to memory");
+    String::from_utf8(jsonl).expect("JSON should be UTF-8")
+}
+
+/// Write `json_lines` a row at a time.
+pub fn write_json_lines<W: Write>(out: &mut W, columns: &[Column], rows: &[Vec<Value>]) -> io::Result<()> {
+    for row in rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .zip(row.iter())
+            .map(|(column, value)| format!("{}:{}", json_string(column.name), json_value(value)))
+            .collect();
+        writeln!(out, "{{{}}}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// The columns' names and types, to go along with JSON lines: `{"columns":[{"name":"repo_id","type":"int64"}]}`.
+/// Every column can be null.
+pub fn json_schema(columns: &[Column]) -> String {
+    #[derive(Serialize)]
+    struct Schema<'a> {
+        columns: &'a [Column],
+    }
+    serde_json::to_string(&Schema { columns: columns }).expect("Couldn't make JSON of the schema")
+}
+
+fn json_value(value: &Value) -> String {
+    match *value {
+        Value::Null => "null".to_string(),
+        Value::Int64(_) | Value::Boolean(_) => plain_text(value),
+        Value::Utf8(ref text) => json_string(text),
+        Value::Timestamp(_) | Value::Date(_) => json_string(&plain_text(value)),
+    }
+}
+
+fn json_string(text: &str) -> String {
+    serde_json::to_string(text).expect("Couldn't make JSON of a string")
+}
+
+fn plain_text(value: &Value) -> String {
+    match *value {
+        Value::Null => "".to_string(),
+        Value::Int64(number) => number.to_string(),
+        Value::Boolean(flag) => flag.to_string(),
+        Value::Utf8(ref text) => text.clone(),
+        Value::Timestamp(time) => time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        Value::Date(date) => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Read back a `.csv` or `.jsonl` file of our output, compressed or not.
+pub fn read_table_file(path: &Path) -> Result<Vec<TextRow>, String> {
+    let text = read_output_file(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    match table_file_format(path) {
+        Some("csv") => read_csv(&text),
+        Some(_) => read_json_lines(&text),
+        None => Err(format!("{} isn't CSV or JSON lines", path.display())),
+    }
+}
+
+/// `csv` or `jsonl` for CSV and JSON lines output, compressed or not.
+pub fn table_file_format(path: &Path) -> Option<&'static str> {
+    let name = path.to_string_lossy();
+    let name = name.trim_end_matches(".gz").trim_end_matches(".zst");
+    if name.ends_with(".csv") {
+        Some("csv")
+    } else if name.ends_with(".jsonl") {
+        Some("jsonl")
+    } else {
+        None
+    }
+}
+
+/// Read CSV as `write_csv` writes it.
+pub fn read_csv(text: &str) -> Result<Vec<TextRow>, String> {
+    let mut records = csv_records(text)?.into_iter();
+    let header = match records.next() {
+        Some(header) => header,
+        None => return Ok(Vec::new()),
+    };
+    records
+        .enumerate()
+        .map(|(index, fields)| {
+            if fields.len() != header.len() {
+                return Err(format!("CSV row {} has {} fields, the header has {}", index + 1, fields.len(), header.len()));
+            }
+            Ok(header
+                .iter()
+                .cloned()
+                .zip(fields.into_iter())
+                .filter(|&(_, ref value)| value != "")
+                .collect())
+        })
+        .collect()
+}
+
+/// Split CSV into records of fields, unquoting quoted fields.
+fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') => if chars.peek() == Some(&'"') {
+                chars.next();
+                field.push('"');
+            } else {
+                quoted = false;
+            },
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => fields.push(field.split_off(0)),
+            (false, '\n') => {
+                fields.push(field.split_off(0));
+                records.push(fields.split_off(0));
+            }
+            (false, '\r') => (),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("CSV ends inside a quoted field".to_string());
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push(fields);
+    }
+    Ok(records)
+}
+
+/// Read JSON lines as `write_json_lines` writes them.
+pub fn read_json_lines(text: &str) -> Result<Vec<TextRow>, String> {
+    text.lines()
+        .filter(|line| line.trim() != "")
+        .enumerate()
+        .map(|(index, line)| {
+            let object: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(line).map_err(|e| format!("JSON line {} isn't an object: {}", index + 1, e))?;
+            Ok(object
+                .into_iter()
+                .filter_map(|(name, value)| match value {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(text) => Some((name, text)),
+                    value => Some((name, value.to_string())),
+                })
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use records::Record;
+    use types::{CommitEvent, RepoIdToName};
+    use writers::{csv_rows, json_lines, json_schema, read_csv, read_json_lines, table_file_format};
+
+    #[test]
+    fn quoting() {
+        let mappings = vec![
+            RepoIdToName {
+                repo_id: 1,
+                repo_name: "Pat \"O'Brien\", Esq.".to_string(),
+                event_timestamp: Utc.ymd(2016, 1, 2).and_hms(3, 4, 5),
+            },
+        ];
+        let rows: Vec<_> = mappings.iter().map(|mapping| mapping.values()).collect();
+
+        assert_eq!(
+            "repo_id,repo_name,event_timestamp\n1,\"Pat \"\"O'Brien\"\", Esq.\",2016-01-02T03:04:05Z\n",
+            csv_rows(&RepoIdToName::columns(), &rows)
+        );
+        assert_eq!(
+            "{\"repo_id\":1,\"repo_name\":\"Pat \\\"O'Brien\\\", Esq.\",\"event_timestamp\":\"2016-01-02T03:04:05Z\"}\n",
+            json_lines(&RepoIdToName::columns(), &rows)
+        );
+    }
+
+    #[test]
+    fn nulls_and_schema() {
+        let committers = vec![
+            CommitEvent {
+                actor: "foo".to_string(),
+                repo_id: 1,
+                period: None,
+                is_bot: false,
+            },
+            CommitEvent {
+                actor: "bar".to_string(),
+                repo_id: 2,
+                period: Some(NaiveDate::from_ymd(2017, 3, 1)),
+                is_bot: true,
+            },
+        ];
+        let rows: Vec<_> = committers.iter().map(|committer| committer.values()).collect();
+
+        assert_eq!(
+            "repo_id,actor_name,period,is_bot\n1,foo,,false\n2,bar,2017-03-01,true\n",
+            csv_rows(&CommitEvent::columns(), &rows)
+        );
+        assert_eq!(
+            "{\"repo_id\":1,\"actor_name\":\"foo\",\"period\":null,\"is_bot\":false}\n\
+             {\"repo_id\":2,\"actor_name\":\"bar\",\"period\":\"2017-03-01\",\"is_bot\":true}\n",
+            json_lines(&CommitEvent::columns(), &rows)
+        );
+        assert_eq!(
+            "{\"columns\":[{\"name\":\"repo_id\",\"type\":\"int64\"},{\"name\":\"actor_name\",\"type\":\"utf8\"},\
+             {\"name\":\"period\",\"type\":\"date\"},{\"name\":\"is_bot\",\"type\":\"boolean\"}]}",
+            json_schema(&CommitEvent::columns())
+        );
+    }
+
+    #[test]
+    fn read_back() {
+        let committers = vec![
+            CommitEvent {
+                actor: "Pat \"O'Brien\",\nEsq.".to_string(),
+                repo_id: 1,
+                period: None,
+                is_bot: false,
+            },
+            CommitEvent {
+                actor: "bar".to_string(),
+                repo_id: 2,
+                period: Some(NaiveDate::from_ymd(2017, 3, 1)),
+                is_bot: true,
+            },
+        ];
+        let rows: Vec<_> = committers.iter().map(|committer| committer.values()).collect();
+
+        for read in vec![
+            read_csv(&csv_rows(&CommitEvent::columns(), &rows)).unwrap(),
+            read_json_lines(&json_lines(&CommitEvent::columns(), &rows)).unwrap(),
+        ] {
+            assert_eq!(2, read.len());
+            assert_eq!("Pat \"O'Brien\",\nEsq.", read[0]["actor_name"]);
+            assert_eq!("1", read[0]["repo_id"]);
+            assert_eq!(None, read[0].get("period"));
+            assert_eq!("2017-03-01", read[1]["period"]);
+            assert_eq!("true", read[1]["is_bot"]);
+        }
+        assert!(read_csv("repo_id,actor_name\n1,\"foo").is_err());
+
+        assert_eq!(Some("csv"), table_file_format(Path::new("stats/1.csv")));
+        assert_eq!(Some("jsonl"), table_file_format(Path::new("stats/1.jsonl.zst")));
+        assert_eq!(Some("csv"), table_file_format(Path::new("stats/1.csv.gz")));
+        assert_eq!(None, table_file_format(Path::new("stats.txt.gz")));
+        assert_eq!(None, table_file_format(Path::new("stats/schema.json")));
+    }
+}